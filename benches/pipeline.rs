@@ -0,0 +1,136 @@
+use std::io::Cursor;
+use apixels::colors::Rgb24;
+use apixels::dominant::{self, AlphaMode};
+use apixels::edges::{self, EdgeResolution};
+use apixels::linear::Blending;
+use apixels::luma::Luma;
+use apixels::picker::Picker;
+use apixels::preprocess::Pipeline;
+use apixels::{into_ascii_controlled, ColourDepth, Ramp};
+use clap::ValueEnum;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::imageops::FilterType;
+use image::{ImageOutputFormat, RgbaImage};
+
+/// The sizes (width, with the ladybug's aspect ratio) that everything gets benchmarked at.
+const WIDTHS: [u32; 3] = [256, 1024, 2048];
+
+fn ladybug(width: u32) -> RgbaImage {
+    let img = image::open(concat!(env!("CARGO_MANIFEST_DIR"), "/Ladybug-header.jpg")).unwrap().into_rgba8();
+    let height = img.height() * width / img.width();
+
+    image::imageops::resize(&img, width, height, FilterType::Triangle)
+}
+
+fn encode(img: &RgbaImage, format: ImageOutputFormat) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    image::DynamicImage::ImageRgba8(img.clone()).write_to(&mut Cursor::new(&mut bytes), format).unwrap();
+
+    bytes
+}
+
+fn render(bytes: &[u8], depth: ColourDepth) -> Vec<u8> {
+    let mut output = Vec::new();
+
+    into_ascii_controlled(
+        bytes.to_vec(),
+        (3.0, 3.0, EdgeResolution::Full),
+        (2, 3),
+        (false, None),
+        false,
+        Ramp::Ascii,
+        (depth, 256, None),
+        (AlphaMode::Empty, Rgb24::from((0, 0, 0)), 0.5),
+        Picker::ModeMean.picker(),
+        (Blending::Srgb, Luma::default()),
+        &Pipeline::new(),
+        None,
+        1 << 30,
+        &mut output,
+    )
+    .unwrap();
+
+    output
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+
+    for width in WIDTHS {
+        let img = ladybug(width);
+        group.throughput(Throughput::Elements((img.width() * img.height()) as u64));
+
+        for (name, format) in [("png", ImageOutputFormat::Png), ("jpeg", ImageOutputFormat::Jpeg(90))] {
+            let bytes = encode(&img, format);
+
+            group.bench_with_input(BenchmarkId::new(name, width), &bytes, |b, bytes| {
+                b.iter(|| image::load_from_memory(bytes).unwrap())
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn edge_detection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("edges");
+
+    for width in WIDTHS {
+        let img = ladybug(width);
+        group.throughput(Throughput::Elements((img.width() * img.height()) as u64));
+
+        for resolution in EdgeResolution::value_variants() {
+            let name = resolution.to_possible_value().unwrap();
+
+            group.bench_with_input(BenchmarkId::new(name.get_name(), width), &img, |b, img| {
+                b.iter(|| edges::difference_of_gaussians(img.as_raw(), img.dimensions(), (3.0, 3.0, *resolution), (2, 3), (Blending::Srgb, Luma::default())))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn dominant_colours(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dominant");
+
+    let img = ladybug(1024);
+
+    // A busy 8×16 sample from the middle of the image
+    let sample: Vec<u8> = image::imageops::crop_imm(&img, img.width() / 2, img.height() / 2, 8, 16)
+        .to_image()
+        .into_raw();
+
+    for picker in Picker::value_variants() {
+        let name = picker.to_possible_value().unwrap();
+
+        for blending in [Blending::Srgb, Blending::Linear] {
+            group.bench_function(BenchmarkId::new(name.get_name(), format!("{blending:?}")), |b| {
+                b.iter(|| dominant::pick(&sample, AlphaMode::Empty, Rgb24::from((0, 0, 0)), picker.picker(), blending, None))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn formatting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+
+    for width in WIDTHS {
+        let bytes = encode(&ladybug(width), ImageOutputFormat::Png);
+
+        for depth in ColourDepth::value_variants() {
+            let name = depth.to_possible_value().unwrap();
+
+            group.bench_with_input(BenchmarkId::new(name.get_name(), width), &bytes, |b, bytes| b.iter(|| render(bytes, *depth)));
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, decode, edge_detection, dominant_colours, formatting);
+criterion_main!(benches);
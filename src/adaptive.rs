@@ -0,0 +1,125 @@
+use clap::ValueEnum;
+use color_thief::{Color as CtColor};
+use crate::colors::Rgb24;
+use crate::dominant::{self, AlphaMode};
+use crate::linear::Blending;
+use crate::picker::{ColourPicker, KMeans, Sample};
+
+/// The glyphs used to draw more than one "pixel" per character in detailed parts of the image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SubCell {
+    /// 2×2 block elements (▘▝▖▗ and friends)
+    Quadrant,
+    /// 2×4 braille dots
+    Braille,
+}
+
+const QUADRANTS: [char; 16] = [' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█'];
+
+impl SubCell {
+    /// How many columns and rows each cell is split into.
+    pub fn grid(self) -> (u32, u32) {
+        match self {
+            SubCell::Quadrant => (2, 2),
+            SubCell::Braille => (2, 4),
+        }
+    }
+
+    /// The glyph with the given parts filled in. Bit `row * columns + column` of `mask` is set when
+    /// that part of the cell should be filled.
+    pub fn glyph(self, mask: u8) -> char {
+        match self {
+            SubCell::Quadrant => QUADRANTS[(mask & 0b1111) as usize],
+            SubCell::Braille => {
+                // Braille dots aren't numbered left to right, top to bottom, so they need shuffling around
+                const DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+                let dots: u32 = DOTS
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| mask & (1 << bit) != 0)
+                    .map(|(_, dot)| dot)
+                    .sum();
+
+                char::from_u32(0x2800 + dots).unwrap()
+            }
+        }
+    }
+
+    /// The mask [`SubCell::glyph`] would turn into `glyph`, if it's one of this kind of glyph.
+    pub fn mask(self, glyph: char) -> Option<u8> {
+        let (columns, rows) = self.grid();
+
+        (0..1u16 << (columns * rows)).map(|mask| mask as u8).find(|mask| self.glyph(*mask) == glyph)
+    }
+
+    /// The glyph that fills the whole cell.
+    pub fn full(self) -> char {
+        match self {
+            SubCell::Quadrant => '█',
+            SubCell::Braille => '⣿',
+        }
+    }
+}
+
+/// Splits a sample (RGBA pixels, `width` by `height`) into the parts of a `sub_cell` glyph and
+/// sorts them into two colours with k-means. Returns the mask of the parts in the second colour,
+/// and the colours, in the same order as a [`ColourPicker`] would: the one that covers the most of
+/// the cell first. Returns `None` if every part ended up the same colour (or if any of them were
+/// skipped for being transparent), in which case a flat fill will do.
+pub fn split(
+    pixels: &[u8],
+    (width, height): (u32, u32),
+    sub_cell: SubCell,
+    (alpha, matte): (AlphaMode, Rgb24),
+    blending: Blending,
+) -> Option<(u8, [CtColor; 2])> {
+    let (columns, rows) = sub_cell.grid();
+
+    // Every part gets at least one pixel, even if the sample is smaller than the grid
+    let span = |part: u32, parts: u32, size: u32| {
+        let start = (part * size / parts).min(size - 1);
+
+        start..((part + 1) * size / parts).max(start + 1)
+    };
+
+    let parts: Vec<[u8; 3]> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .filter_map(|(column, row)| {
+            let part: Vec<u8> = span(row, rows, height)
+                .flat_map(|y| span(column, columns, width).map(move |x| (x, y)))
+                .flat_map(|(x, y)| {
+                    let idx = ((y * width + x) * 4) as usize;
+
+                    [pixels[idx], pixels[idx + 1], pixels[idx + 2], pixels[idx + 3]]
+                })
+                .collect();
+
+            let part = dominant::flatten(&part, alpha, matte);
+
+            (!part.is_empty()).then(|| blending.mean(&part))
+        })
+        .collect();
+
+    // Some parts were completely transparent and skipped, so the mask wouldn't line up
+    if parts.len() != (columns * rows) as usize {
+        return None;
+    }
+
+    let colours = KMeans.pick(&Sample::new(&parts), blending);
+    let [first, second] = colours.map(|colour| [colour.r, colour.g, colour.b]);
+
+    let distance = |a: [u8; 3], b: [u8; 3]| a.iter().zip(b).map(|(a, b)| (a.abs_diff(b) as u32).pow(2)).sum::<u32>();
+
+    let mask = parts
+        .iter()
+        .enumerate()
+        .filter(|(_, part)| distance(**part, second) < distance(**part, first))
+        .fold(0u8, |mask, (bit, _)| mask | 1 << bit);
+
+    if mask == 0 || mask.count_ones() == columns * rows {
+        return None;
+    }
+
+    Some((mask, colours))
+}
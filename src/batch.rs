@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use anyhow::bail;
+use image::ImageFormat;
+
+/// A file to convert, and the directory it was found in relative to whatever was being searched, so
+/// that the outputs can be laid out the same way.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Input {
+    pub path: PathBuf,
+    pub directory: PathBuf,
+}
+
+/// Whether a file found while searching a directory is something apixels can draw: an image the
+/// `image` crate knows, or ANSI art.
+fn is_drawable(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok() || path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ans"))
+}
+
+/// Whether `name` matches `pattern`, where `*` is any number of characters, `?` is any one character
+/// and `[...]` is one of the characters in the brackets (or not in them, starting with `!`), with
+/// `a-z` style ranges.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Where to go back to (just after the last `*`, and how much of the name it had eaten) if the rest doesn't match
+    let mut backtrack = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => {
+                (p, n) = (p + 1, n + 1);
+                continue;
+            }
+            Some('[') => {
+                if let Some((matched, end)) = class(&pattern[p + 1..], name[n]) {
+                    if matched {
+                        (p, n) = (p + 1 + end + 1, n + 1);
+                        continue;
+                    }
+                } else if name[n] == '[' {
+                    // No closing bracket, so it's just a bracket
+                    (p, n) = (p + 1, n + 1);
+                    continue;
+                }
+            }
+            Some(character) if *character == name[n] => {
+                (p, n) = (p + 1, n + 1);
+                continue;
+            }
+            _ => {}
+        }
+
+        match backtrack {
+            Some((after_star, eaten)) => {
+                backtrack = Some((after_star, eaten + 1));
+                (p, n) = (after_star, eaten + 1);
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|character| *character == '*')
+}
+
+/// Matches a character against a bracket expression (everything after the `[`). Returns whether it
+/// matched, and where the `]` is, or `None` if there isn't one.
+fn class(pattern: &[char], character: char) -> Option<(bool, usize)> {
+    let negated = pattern.first() == Some(&'!');
+    let start = negated as usize;
+
+    // A `]` straight after the `[` (or `[!`) is part of the set rather than the end of it
+    let end = start + 1 + pattern.get(start + 1..)?.iter().position(|character| *character == ']')?;
+    let set = &pattern[start..end];
+
+    let mut matched = false;
+    let mut index = 0;
+
+    while index < set.len() {
+        if index + 2 < set.len() && set[index + 1] == '-' {
+            matched |= (set[index]..=set[index + 2]).contains(&character);
+            index += 3;
+        } else {
+            matched |= set[index] == character;
+            index += 1;
+        }
+    }
+
+    Some((matched != negated, end))
+}
+
+fn is_pattern(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+/// Adds the drawable files in `directory` (and its subdirectories, if `recursive`) to `inputs`, in
+/// order of their names.
+fn search(root: &Path, directory: &Path, recursive: bool, inputs: &mut Vec<Input>) -> anyhow::Result<()> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            if recursive {
+                search(root, &path, recursive, inputs)?;
+            }
+        } else if is_drawable(&path) {
+            inputs.push(Input {
+                directory: directory.strip_prefix(root).unwrap_or(Path::new("")).to_path_buf(),
+                path,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds the files matching `components` (the parts of a path, which may be patterns) under
+/// `directory` to `inputs`. `**` matches any number of directories.
+fn glob(root: &Path, directory: &Path, components: &[&str], inputs: &mut Vec<Input>) -> anyhow::Result<()> {
+    let Some((first, rest)) = components.split_first() else {
+        return Ok(());
+    };
+
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+
+    entries.sort();
+
+    if *first == "**" {
+        // Either it stands for no directories at all, or for this one and maybe more
+        glob(root, directory, rest, inputs)?;
+
+        for path in entries.iter().filter(|path| path.is_dir()) {
+            glob(root, path, components, inputs)?;
+        }
+
+        return Ok(());
+    }
+
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        // Hidden files only match patterns that ask for them, like the shell
+        if !matches(first, &name) || (name.starts_with('.') && !first.starts_with('.')) {
+            continue;
+        }
+
+        if rest.is_empty() {
+            if path.is_file() {
+                inputs.push(Input {
+                    directory: directory.strip_prefix(root).unwrap_or(Path::new("")).to_path_buf(),
+                    path,
+                });
+            }
+        } else if path.is_dir() {
+            glob(root, &path, rest, inputs)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns the files, directories and patterns given on the command line into the files to convert.
+/// Directories are searched for images (and ANSI art), and patterns are matched against files the
+/// same way the shell would, for when they've been quoted.
+pub fn expand(names: &[String], recursive: bool) -> anyhow::Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+
+    for name in names {
+        let path = Path::new(name);
+
+        if path.is_dir() {
+            search(path, path, recursive, &mut inputs)?;
+        } else if is_pattern(name) {
+            // Everything up to the first part with a pattern in it is just a directory to start from
+            let components: Vec<Component> = path.components().collect();
+            let split = components
+                .iter()
+                .position(|component| is_pattern(&component.as_os_str().to_string_lossy()))
+                .unwrap_or(components.len());
+
+            let root: PathBuf = components[..split].iter().collect();
+            let root = if root.as_os_str().is_empty() { PathBuf::from(".") } else { root };
+
+            let patterns: Vec<String> = components[split..].iter().map(|component| component.as_os_str().to_string_lossy().into_owned()).collect();
+            let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+
+            let before = inputs.len();
+            glob(&root, &root, &patterns, &mut inputs)?;
+
+            if inputs.len() == before {
+                bail!("Nothing matches {name}");
+            }
+        } else {
+            inputs.push(Input {
+                path: path.to_path_buf(),
+                directory: PathBuf::new(),
+            });
+        }
+    }
+
+    Ok(inputs)
+}
+
+/// Where the output for `input` goes: `template` (with `{stem}`, `{name}` and `{ext}` filled in)
+/// inside `output_directory`, in the same subdirectory the input was found in.
+pub fn output_path(input: &Input, template: &str, extension: &str, output_directory: &Path) -> PathBuf {
+    let name = input.path.file_name().unwrap_or_default().to_string_lossy();
+    let stem = input.path.file_stem().unwrap_or_default().to_string_lossy();
+
+    let file_name = template
+        .replace("{stem}", &stem)
+        .replace("{name}", &name)
+        .replace("{ext}", extension);
+
+    output_directory.join(&input.directory).join(file_name)
+}
+
+/// The outputs for every input, as [`output_path`] gives them. Two inputs going to the same place
+/// (like `logo.png` and `logo.bmp` with the default template) would overwrite each other, so that's
+/// an error.
+pub fn output_paths(inputs: &[Input], template: &str, extension: &str, output_directory: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut seen: HashMap<PathBuf, &Path> = HashMap::new();
+
+    inputs
+        .iter()
+        .map(|input| {
+            let path = output_path(input, template, extension, output_directory);
+
+            if let Some(other) = seen.insert(path.clone(), &input.path) {
+                bail!(
+                    "{} and {} would both be written to {}, put `{{name}}` in `--name-template` to keep them apart",
+                    other.display(),
+                    input.path.display(),
+                    path.display(),
+                );
+            }
+
+            Ok(path)
+        })
+        .collect()
+}
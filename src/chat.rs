@@ -0,0 +1,188 @@
+use std::io::Write;
+use crate::colors::Rgb24;
+use crate::export::nearest;
+use crate::grid::{Cell, Grid};
+
+/// mIRC's first 16 colours, which every IRC client knows.
+const MIRC_COLOURS: [u32; 16] = [
+    0xffffff, 0x000000, 0x00007f, 0x009300, 0xff0000, 0x7f0000, 0x9c009c, 0xfc7f00,
+    0xffff00, 0x00fc00, 0x009393, 0x00ffff, 0x0000fc, 0xff00ff, 0x7f7f7f, 0xd2d2d2,
+];
+
+/// The 83 colours that newer clients added after those, making 99. (Colour 99 is the client's default.)
+const MIRC_EXTENDED: [u32; 83] = [
+    0x470000, 0x472100, 0x474700, 0x324700, 0x004700, 0x00472c, 0x004747, 0x002747, 0x000047, 0x2e0047, 0x470047, 0x47002a,
+    0x740000, 0x743a00, 0x747400, 0x517400, 0x007400, 0x007449, 0x007474, 0x004074, 0x000074, 0x4b0074, 0x740074, 0x740045,
+    0xb50000, 0xb56300, 0xb5b500, 0x7db500, 0x00b500, 0x00b571, 0x00b5b5, 0x0063b5, 0x0000b5, 0x7500b5, 0xb500b5, 0xb5006b,
+    0xff0000, 0xff8c00, 0xffff00, 0xb2ff00, 0x00ff00, 0x00ffa0, 0x00ffff, 0x008cff, 0x0000ff, 0xa500ff, 0xff00ff, 0xff0098,
+    0xff5959, 0xffb459, 0xffff71, 0xcfff60, 0x6fff6f, 0x65ffc9, 0x6dffff, 0x59b4ff, 0x5959ff, 0xc459ff, 0xff66ff, 0xff59bc,
+    0xff9c9c, 0xffd39c, 0xffff9c, 0xe2ff9c, 0x9cff9c, 0x9cffdb, 0x9cffff, 0x9cd3ff, 0x9c9cff, 0xdc9cff, 0xff9cff, 0xff94d3,
+    0x000000, 0x131313, 0x282828, 0x363636, 0x4d4d4d, 0x656565, 0x818181, 0x9f9f9f, 0xbcbcbc, 0xe2e2e2, 0xffffff,
+];
+
+/// The mIRC colour that means "whatever the client would normally use".
+const MIRC_DEFAULT: u8 = 99;
+
+/// What Discord draws SGR 30 to 37 as. They aren't the usual ANSI colours at all.
+const DISCORD_FOREGROUNDS: [u32; 8] = [0x4f545c, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xffffff];
+
+/// What Discord draws SGR 40 to 47 as, which are different again.
+const DISCORD_BACKGROUNDS: [u32; 8] = [0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3];
+
+fn rgb(hex: &u32) -> Rgb24 {
+    Rgb24::from(((hex >> 16) as u8, (hex >> 8) as u8, *hex as u8))
+}
+
+/// Characters that can all go out after one colour code.
+struct Run<T> {
+    foreground: Option<T>,
+    background: Option<T>,
+    text: String,
+}
+
+/// Splits a row into runs of characters in the same colours, once they've been turned into the
+/// platform's colours. Spaces don't show their foreground, so they go along with whatever's next to
+/// them, and spaces on the end without a background are left off, to keep messages short.
+fn runs<T: Copy + PartialEq>(row: &[Cell], (foreground, background): (impl Fn(Rgb24) -> T, impl Fn(Rgb24) -> T)) -> Vec<Run<T>> {
+    let mut runs: Vec<Run<T>> = Vec::new();
+
+    for cell in row {
+        let colours = (cell.foreground.map(&foreground), cell.background.map(&background));
+        let blank = cell.character.is_whitespace();
+
+        match runs.last_mut() {
+            Some(run) if run.background == colours.1 && (blank || run.foreground == colours.0) => run.text.push(cell.character),
+            // A run that's only spaces so far can take on the foreground of what comes after it
+            Some(run) if run.background == colours.1 && run.text.chars().all(char::is_whitespace) => {
+                run.foreground = colours.0;
+                run.text.push(cell.character);
+            }
+            _ => runs.push(Run {
+                foreground: colours.0,
+                background: colours.1,
+                text: cell.character.to_string(),
+            }),
+        }
+    }
+
+    if let Some(run) = runs.last_mut().filter(|run| run.background.is_none()) {
+        run.text.truncate(run.text.trim_end().len());
+    }
+
+    runs
+}
+
+/// Writes the grid out with mIRC colour codes, as one message for each row. `colours` is either 16,
+/// for the colours every client has, or 99 for the newer extended palette.
+pub fn irc(grid: &Grid, colours: u8, output: &mut impl Write) -> std::io::Result<()> {
+    let extended: &[u32] = if colours == 99 { &MIRC_EXTENDED } else { &[] };
+    let palette: Vec<Rgb24> = MIRC_COLOURS.iter().chain(extended).map(rgb).collect();
+
+    let colour = |colour| nearest(&palette, colour);
+
+    for y in 0..grid.height {
+        // Every message starts in the default colours
+        let mut current = (None, None);
+
+        for run in runs(grid.row(y), (colour, colour)) {
+            let colours = (run.foreground, run.background);
+
+            if colours != current {
+                // There's no turning just the background off, so it's all or nothing
+                if colours.1.is_none() && current.1.is_some() || colours == (None, None) {
+                    output.write_all(b"\x0f")?;
+                }
+
+                // Always using two digits stops the text from being read as part of the code if it starts with one
+                match colours {
+                    (foreground, Some(background)) => write!(output, "\x03{:02},{background:02}", foreground.unwrap_or(MIRC_DEFAULT))?,
+                    (Some(foreground), None) => {
+                        write!(output, "\x03{foreground:02}")?;
+
+                        // A comma straight afterwards would be read as the start of a background, unless
+                        // there's something (like bold on and off again) in between
+                        if run.text.starts_with(',') {
+                            output.write_all(b"\x02\x02")?;
+                        }
+                    }
+                    (None, None) => {}
+                }
+
+                current = colours;
+            }
+
+            output.write_all(run.text.as_bytes())?;
+        }
+
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the grid out as a Discord ```ansi code block. Discord only understands the 8 foreground
+/// and 8 background colours (in its own shades of them), so everything gets one of those.
+pub fn discord(grid: &Grid, output: &mut impl Write) -> std::io::Result<()> {
+    let foregrounds: Vec<Rgb24> = DISCORD_FOREGROUNDS.iter().map(rgb).collect();
+    let backgrounds: Vec<Rgb24> = DISCORD_BACKGROUNDS.iter().map(rgb).collect();
+
+    writeln!(output, "```ansi")?;
+
+    let mut current = (None, None);
+
+    for y in 0..grid.height {
+        for run in runs(grid.row(y), (|colour| nearest(&foregrounds, colour), |colour| nearest(&backgrounds, colour))) {
+            let colours = (run.foreground, run.background);
+
+            if colours != current {
+                write!(output, "\x1b[0")?;
+
+                if let Some(foreground) = colours.0 {
+                    write!(output, ";{}", 30 + foreground)?;
+                }
+
+                if let Some(background) = colours.1 {
+                    write!(output, ";{}", 40 + background)?;
+                }
+
+                write!(output, "m")?;
+                current = colours;
+            }
+
+            output.write_all(run.text.as_bytes())?;
+        }
+
+        writeln!(output)?;
+    }
+
+    writeln!(output, "```")
+}
+
+/// Writes the grid out with BBCode `[color]` tags. BBCode has no backgrounds, so blank cells with one
+/// become full blocks in that colour instead.
+pub fn bbcode(grid: &Grid, output: &mut impl Write) -> std::io::Result<()> {
+    let cells: Vec<Cell> = grid.cells
+        .iter()
+        .map(|cell| match cell.background {
+            Some(background) if cell.character.is_whitespace() => Cell {
+                character: '█',
+                foreground: Some(background),
+                background: None,
+            },
+            _ => Cell { background: None, ..*cell },
+        })
+        .collect();
+
+    for row in cells.chunks(grid.width.max(1)) {
+        for run in runs(row, (|colour| colour, |colour| colour)) {
+            match run.foreground {
+                Some(Rgb24 { r, g, b }) => write!(output, "[color=#{r:02x}{g:02x}{b:02x}]{}[/color]", run.text)?,
+                None => output.write_all(run.text.as_bytes())?,
+            }
+        }
+
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
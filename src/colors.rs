@@ -1,7 +1,40 @@
+use std::str::FromStr;
+use anyhow::{anyhow, bail};
 use color_space::{CompareEuclidean};
+use crate::luma::Luma;
+
+/// Where each channel goes in the `u16` of an [`Rgb565`].
+#[derive(Copy, Clone, Debug, Default, PartialOrd, PartialEq, Eq)]
+pub enum Rgb565Layout {
+    /// Red in the top 5 bits, then green, then blue in the bottom 5 bits. This is what everything
+    /// else means by RGB565.
+    #[default]
+    Rgb,
+    /// Blue in the top 5 bits and red in the bottom 5 bits.
+    Bgr,
+}
 
 #[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
 pub struct Rgb565 {
+    pub rgb: u16,
+    pub layout: Rgb565Layout,
+}
+
+/// 3 bits of red, 3 of green and 2 of blue, from the top bit down.
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+pub struct Rgb332 {
+    pub rgb: u8
+}
+
+/// 4 bits each of red, green and blue, in the bottom 12 bits.
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+pub struct Rgb444 {
+    pub rgb: u16
+}
+
+/// 5 bits each of red, green and blue, in the bottom 15 bits.
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+pub struct Rgb555 {
     pub rgb: u16
 }
 
@@ -37,6 +70,33 @@ pub enum Ansi {
     White
 }
 
+/// One of the xterm 256 colour palette. Only the 6×6×6 colour cube and the greys (16 to 255) are ever
+/// picked by [`Colour::from_rgb`], since every terminal has its own idea of what the first 16 look like.
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+pub struct Ansi256 {
+    pub index: u8
+}
+
+/// The ANSI colours, in the order of their escape codes.
+pub const ANSI_COLOURS: [Ansi; 16] = [
+    Ansi::Black,
+    Ansi::DarkRed,
+    Ansi::DarkGreen,
+    Ansi::DarkYellow,
+    Ansi::DarkBlue,
+    Ansi::DarkMagenta,
+    Ansi::DarkCyan,
+    Ansi::Gray,
+    Ansi::DarkGray,
+    Ansi::Red,
+    Ansi::Green,
+    Ansi::Yellow,
+    Ansi::Blue,
+    Ansi::Purple,
+    Ansi::Cyan,
+    Ansi::White,
+];
+
 pub trait Colour {
     fn into_rgb(&self) -> (u8, u8, u8);
 
@@ -85,43 +145,130 @@ impl Colour for Rgb24 {
     }
 }
 
+/// Rounds an 8 bit channel to the nearest of the `2^bits` evenly spaced levels.
+pub fn quantise(channel: u8, bits: u32) -> u16 {
+    let max = (1u32 << bits) - 1;
+
+    ((channel as u32 * max + 127) / 255) as u16
+}
+
+/// Turns a level from [`quantise`] back into an 8 bit channel, so that the highest level is 255.
+pub fn expand(level: u16, bits: u32) -> u8 {
+    let max = (1u32 << bits) - 1;
+
+    ((level as u32 * 255 + max / 2) / max) as u8
+}
+
+impl Rgb565 {
+    pub fn with_layout(colour: Rgb24, layout: Rgb565Layout) -> Self {
+        Self::pack((quantise(colour.r, 5), quantise(colour.g, 6), quantise(colour.b, 5)), layout)
+    }
+
+    fn pack((r, g, b): (u16, u16, u16), layout: Rgb565Layout) -> Self {
+        let rgb = match layout {
+            Rgb565Layout::Rgb => r << 11 | g << 5 | b,
+            Rgb565Layout::Bgr => b << 11 | g << 5 | r,
+        };
+
+        Self { rgb, layout }
+    }
+
+    /// The 5 bit red, 6 bit green and 5 bit blue levels, whatever the layout.
+    pub fn channels(&self) -> (u16, u16, u16) {
+        let (high, green, low) = (self.rgb >> 11, (self.rgb >> 5) & 0b111111, self.rgb & 0b11111);
+
+        match self.layout {
+            Rgb565Layout::Rgb => (high, green, low),
+            Rgb565Layout::Bgr => (low, green, high),
+        }
+    }
+
+    /// The same colour packed the other way around (or not, if it's already in that layout).
+    pub fn to_layout(self, layout: Rgb565Layout) -> Self {
+        Self::pack(self.channels(), layout)
+    }
+}
+
 impl Colour for Rgb565 {
     fn into_rgb(&self) -> (u8, u8, u8) {
-        let r_i = 0b0000000000011111 & self.rgb;
-        let g_i = (0b0000011111100000 & self.rgb) >> 5;
-        let b_i = (0b1111100000000000 & self.rgb) >> 11;
+        let (r, g, b) = self.channels();
 
-        let r = r_i as f64 * 8.22580645161;
-        let g = g_i as f64 * 4.04761904762;
-        let b = b_i as f64 * 8.22580645161;
+        (expand(r, 5), expand(g, 6), expand(b, 5))
+    }
 
-        (r as u8, g as u8, b as u8)
+    fn from_rgb(colour: Rgb24) -> Self {
+        Self::with_layout(colour, Rgb565Layout::default())
+    }
+}
+
+impl Colour for Rgb332 {
+    fn into_rgb(&self) -> (u8, u8, u8) {
+        let rgb = self.rgb as u16;
+
+        (expand(rgb >> 5, 3), expand((rgb >> 2) & 0b111, 3), expand(rgb & 0b11, 2))
     }
 
     fn from_rgb(colour: Rgb24) -> Self {
-        // Safety: value will never be NaN or Infinity, and u8 will always be smaller than u16.
-        let r: u16 = unsafe { (colour.r as f64 / 8.22580645161).to_int_unchecked::<u16>() & 0b0000000000011111 };
-        let g: u16 = unsafe { (colour.g as f64 / 4.04761904762).to_int_unchecked::<u16>() & 0b0000000000111111 };
-        let b: u16 = unsafe { (colour.b as f64 / 8.22580645161).to_int_unchecked::<u16>() & 0b0000000000011111 };
+        Self {
+            rgb: (quantise(colour.r, 3) << 5 | quantise(colour.g, 3) << 2 | quantise(colour.b, 2)) as u8
+        }
+    }
+}
+
+impl Colour for Rgb444 {
+    fn into_rgb(&self) -> (u8, u8, u8) {
+        (expand((self.rgb >> 8) & 0b1111, 4), expand((self.rgb >> 4) & 0b1111, 4), expand(self.rgb & 0b1111, 4))
+    }
 
+    fn from_rgb(colour: Rgb24) -> Self {
         Self {
-            rgb: r | g << 5 | b << 11
+            rgb: quantise(colour.r, 4) << 8 | quantise(colour.g, 4) << 4 | quantise(colour.b, 4)
         }
     }
 }
 
-impl Colour for Monochrome {
+impl Colour for Rgb555 {
     fn into_rgb(&self) -> (u8, u8, u8) {
-        (self.intensity, self.intensity, self.intensity)
+        (expand((self.rgb >> 10) & 0b11111, 5), expand((self.rgb >> 5) & 0b11111, 5), expand(self.rgb & 0b11111, 5))
     }
 
     fn from_rgb(colour: Rgb24) -> Self {
-        let brightness = (colour.r as u16 + colour.g as u16 + colour.b as u16).div_floor(3);
+        Self {
+            rgb: quantise(colour.r, 5) << 10 | quantise(colour.g, 5) << 5 | quantise(colour.b, 5)
+        }
+    }
+}
 
+impl Monochrome {
+    /// The grey with the same brightness as `colour`, going by `luma`.
+    pub fn with_luma(colour: Rgb24, luma: Luma) -> Self {
         Monochrome {
-            intensity: brightness as u8
+            intensity: luma.luma(colour.into_rgb())
         }
     }
+
+    /// Like [`Monochrome::with_luma`], but with only `levels` evenly spaced shades of grey (black
+    /// and white included) to choose from. 256 levels is the same as `with_luma`.
+    pub fn with_levels(colour: Rgb24, luma: Luma, levels: u16) -> Self {
+        let max = levels.clamp(2, 256) as u32 - 1;
+        let intensity = Self::with_luma(colour, luma).intensity as u32;
+
+        let level = (intensity * max + 127) / 255;
+
+        Monochrome {
+            intensity: ((level * 255 + max / 2) / max) as u8
+        }
+    }
+}
+
+impl Colour for Monochrome {
+    fn into_rgb(&self) -> (u8, u8, u8) {
+        (self.intensity, self.intensity, self.intensity)
+    }
+
+    fn from_rgb(colour: Rgb24) -> Self {
+        Self::with_luma(colour, Luma::default())
+    }
 }
 
 impl Colour for Ansi {
@@ -148,26 +295,7 @@ impl Colour for Ansi {
     }
 
     fn from_rgb(colour: Rgb24) -> Self {
-        let colours = [
-            Ansi::Black,
-            Ansi::DarkRed,
-            Ansi::DarkGreen,
-            Ansi::DarkYellow,
-            Ansi::DarkBlue,
-            Ansi::DarkMagenta,
-            Ansi::DarkCyan,
-            Ansi::Gray,
-            Ansi::DarkGray,
-            Ansi::Red,
-            Ansi::Green,
-            Ansi::Yellow,
-            Ansi::Blue,
-            Ansi::Purple,
-            Ansi::Cyan,
-            Ansi::White,
-        ];
-
-        let colour = colours.into_iter().reduce(|last, current| {
+        let colour = ANSI_COLOURS.into_iter().reduce(|last, current| {
             let last_distance = last.colour_distance(colour);
 
             let current_distance = current.colour_distance(colour);
@@ -183,6 +311,54 @@ impl Colour for Ansi {
     }
 }
 
+/// The levels of each channel in the 256 colour palette's colour cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Colour for Ansi256 {
+    fn into_rgb(&self) -> (u8, u8, u8) {
+        match self.index {
+            0..=15 => ANSI_COLOURS[self.index as usize].into_rgb(),
+            16..=231 => {
+                let cube = (self.index - 16) as usize;
+
+                (CUBE_LEVELS[cube / 36], CUBE_LEVELS[cube / 6 % 6], CUBE_LEVELS[cube % 6])
+            }
+            _ => {
+                let grey = 8 + (self.index - 232) * 10;
+
+                (grey, grey, grey)
+            }
+        }
+    }
+
+    fn from_rgb(colour: Rgb24) -> Self {
+        let nearest_level = |channel: u8| {
+            CUBE_LEVELS
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, level)| level.abs_diff(channel))
+                .unwrap()
+                .0 as u8
+        };
+
+        let cube = Ansi256 {
+            index: 16 + 36 * nearest_level(colour.r) + 6 * nearest_level(colour.g) + nearest_level(colour.b)
+        };
+
+        // The greys go from 8 to 238 in steps of 10
+        let mean = (colour.r as i32 + colour.g as i32 + colour.b as i32) / 3;
+        let grey = Ansi256 {
+            index: 232 + ((mean - 8 + 5) / 10).clamp(0, 23) as u8
+        };
+
+        if grey.colour_distance(colour) < cube.colour_distance(colour) {
+            grey
+        } else {
+            cube
+        }
+    }
+}
+
 impl Colour for (u8, u8, u8) {
     fn into_rgb(&self) -> (u8, u8, u8) {
         *self
@@ -215,4 +391,26 @@ impl From<(u8, u8, u8)> for Rgb24 {
             b,
         }
     }
-}
\ No newline at end of file
+}
+
+impl FromStr for Rgb24 {
+    type Err = anyhow::Error;
+
+    /// Parses `#rrggbb` (the `#` is optional).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+
+        if hex.len() != 6 || !hex.is_ascii() {
+            bail!("Expected a colour in the form #rrggbb, got `{s}`")
+        }
+
+        let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16)
+            .map_err(|_| anyhow!("`{s}` is not a valid hex colour"));
+
+        Ok(Rgb24 {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        })
+    }
+}
@@ -0,0 +1,35 @@
+/// The top half of code page 437, the character set of the original IBM PC, which is what DOS (and so
+/// most ANSI art) used. The bottom half is plain ASCII, apart from 0x7F.
+const HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Turns CP437 bytes into a string. Control characters are left as they are, since ANSI art needs
+/// them for its escape codes and line breaks.
+pub fn decode(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| match byte {
+            0x7f => '⌂',
+            0x80.. => HIGH[(byte - 0x80) as usize],
+            _ => *byte as char,
+        })
+        .collect()
+}
+
+/// The CP437 byte for a character, if there is one. The other control characters (the ones that
+/// CP437 draws as smileys and arrows) are left as control characters, like [`decode`] does.
+pub fn encode(character: char) -> Option<u8> {
+    match character {
+        '⌂' => Some(0x7f),
+        '\0'..='\x7e' => Some(character as u8),
+        _ => HIGH.iter().position(|high| *high == character).map(|position| 0x80 + position as u8),
+    }
+}
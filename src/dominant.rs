@@ -1,44 +1,86 @@
-use std::collections::HashMap;
+use clap::ValueEnum;
 use color_thief::{Color as CtColor};
+use image::{DynamicImage, RgbaImage};
+use crate::colors::Rgb24;
+use crate::linear::Blending;
+use crate::picker::{ColourPicker, ModeMean, Sample};
 
-pub fn two_most_dominant(pixels: &[u8]) -> [CtColor; 2] {
-    let pixel_count = pixels.len();
+/// What to do with the alpha channel of a sample.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AlphaMode {
+    /// Fully transparent pixels are left out of the sample altogether.
+    Skip,
+    /// Every pixel is blended onto the matte colour before sampling.
+    Composite,
+    /// Like `Composite`, but a sample that is mostly transparent becomes an empty cell (see
+    /// [`coverage`]). Where that threshold lies is up to the caller.
+    Empty,
+}
 
-    let [secondary_r, secondary_g, secondary_b]: [u8; 3] = pixels
-            .iter()
-            .map(|x| *x as usize)
-            .array_chunks()
-            .fold(
-                [0, 0, 0],
-                |[r_a, g_a, b_a], [r, g, b]| {
-                                [r_a + r.pow(2), g_a + g.pow(2), b_a + b.pow(2)]
-                })
-        .map(|colour_sum| ((colour_sum / pixel_count) as f64 + 1.0).sqrt() as u8 );
+/// Every `DynamicImage` variant (16 bit, float, luma, luma + alpha...) gets squashed down to
+/// 8 bit RGBA so that the samplers only ever have to deal with one pixel layout.
+pub fn normalise(img: DynamicImage) -> RgbaImage {
+    img.into_rgba8()
+}
 
-    let average = CtColor::new(secondary_r, secondary_g, secondary_b);
+/// How opaque a sample is, from 0 (fully transparent) to 1 (fully opaque).
+pub fn coverage(pixels: &[u8]) -> f32 {
+    let pixel_count = pixels.len() / 4;
 
-    let mut map = HashMap::new();
+    if pixel_count == 0 {
+        return 0.0;
+    }
 
-    // let dominant = pixels.iter().array_chunks().inspect(|[r, g, b]| {
-    //     let entry = map.entry([r, g, b]).or_insert(0u32);
-    //
-    //     *entry += 1
-    // }).map(|_| map.values().max());
+    let alpha_sum: usize = pixels.chunks_exact(4).map(|pixel| pixel[3] as usize).sum();
 
-    for [r, g, b] in pixels.array_chunks() {
-        let entry = map.entry([*r, *g, *b]).or_insert(0u32);
+    alpha_sum as f32 / (pixel_count * 255) as f32
+}
 
-        *entry += 1;
-    }
+/// Takes RGBA pixels and gets rid of the alpha channel, either by throwing away transparent pixels
+/// or by blending them onto `matte`.
+pub fn flatten(pixels: &[u8], alpha: AlphaMode, matte: Rgb24) -> Vec<[u8; 3]> {
+    pixels
+        .chunks_exact(4)
+        .filter(|pixel| alpha != AlphaMode::Skip || pixel[3] != 0)
+        .map(|pixel| {
+            let a = pixel[3] as u16;
+
+            let blend = |channel: u8, matte: u8| ((channel as u16 * a + matte as u16 * (255 - a) + 127) / 255) as u8;
 
-    let [dom_r, dom_g, dom_b] = map
-        .into_iter()
-        .max_by_key(|&(_, count)| count)
-        .map(|(val, _)| val)
-        .expect("Sample size of zero.");
+            match alpha {
+                AlphaMode::Skip => [pixel[0], pixel[1], pixel[2]],
+                AlphaMode::Composite | AlphaMode::Empty => [
+                    blend(pixel[0], matte.r),
+                    blend(pixel[1], matte.g),
+                    blend(pixel[2], matte.b),
+                ],
+            }
+        })
+        .collect()
+}
 
-    // The reason the dominant colour is averaged with the... average... is to reduce jagged edges in the image.
-    let dominant = CtColor::new(((dom_r as u16 + secondary_r as u16) / 2) as u8, ((dom_g as u16 + secondary_g as u16) / 2) as u8, ((dom_b as u16 + secondary_b as u16) / 2) as u8);
+/// Expects RGBA pixels. Returns `None` if there's nothing left to sample once transparent pixels
+/// have been dealt with (only possible with `AlphaMode::Skip`).
+pub fn two_most_dominant(pixels: &[u8], alpha: AlphaMode, matte: Rgb24) -> Option<[CtColor; 2]> {
+    pick(pixels, alpha, matte, &ModeMean, Blending::Srgb, None)
+}
+
+/// Same as [`two_most_dominant`], but with whichever [`ColourPicker`] and [`Blending`] you like.
+/// `sums` are handed on to the picker (see [`Sample::sums`]). They have to match the pixels after
+/// they've been flattened, which in practice means they should only be given for fully opaque samples.
+pub fn pick(
+    pixels: &[u8],
+    alpha: AlphaMode,
+    matte: Rgb24,
+    picker: &dyn ColourPicker,
+    blending: Blending,
+    sums: Option<[u64; 6]>,
+) -> Option<[CtColor; 2]> {
+    let pixels = flatten(pixels, alpha, matte);
+
+    if pixels.is_empty() {
+        return None;
+    }
 
-    [dominant, average]
-}
\ No newline at end of file
+    Some(picker.pick(&Sample { pixels: &pixels, sums }, blending))
+}
@@ -0,0 +1,244 @@
+use clap::ValueEnum;
+use rayon::prelude::*;
+use crate::linear::{self, Blending};
+use crate::luma::Luma;
+
+/// Where the edge detection happens.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, ValueEnum)]
+pub enum EdgeResolution {
+    /// On every pixel of the image
+    #[default]
+    Full,
+    /// On an image with one pixel per character, which is a lot faster, but misses the finer details
+    Cell,
+}
+
+/// A single channel image, stored row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plane {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+}
+
+impl Plane {
+    /// The luma of each pixel (RGBA, `width` pixels to a row), from 0 to 1. With `Blending::Linear`
+    /// the luma is decoded into linear light.
+    pub fn luma(pixels: &[u8], width: u32, (blending, luma): (Blending, Luma)) -> Self {
+        let values: Vec<f32> = pixels
+            .par_chunks_exact(4)
+            .map(|pixel| {
+                let value = luma.luma((pixel[0], pixel[1], pixel[2]));
+
+                match blending {
+                    Blending::Srgb => value as f32 / 255.0,
+                    Blending::Linear => linear::srgb_to_linear(value),
+                }
+            })
+            .collect();
+
+        let height = (values.len() as u32).checked_div(width).unwrap_or(0);
+
+        Self { width, height, values }
+    }
+
+    /// Shrinks the plane so that every `block_width`×`block_height` block becomes one value (its mean).
+    pub fn downscale(&self, block_width: u32, block_height: u32) -> Self {
+        let width = self.width.div_ceil(block_width);
+        let height = self.height.div_ceil(block_height);
+
+        let values = (0..width * height)
+            .into_par_iter()
+            .map(|idx| {
+                let (left, top) = ((idx % width) * block_width, (idx / width) * block_height);
+                let right = (left + block_width).min(self.width);
+                let bottom = (top + block_height).min(self.height);
+
+                let sum: f32 = (top..bottom)
+                    .flat_map(|y| &self.values[(y * self.width + left) as usize..(y * self.width + right) as usize])
+                    .sum();
+
+                sum / ((right - left) * (bottom - top)) as f32
+            })
+            .collect();
+
+        Self { width, height, values }
+    }
+
+    /// Stretches the plane back out to `width`×`height` by repeating each value over its block.
+    pub fn upscale(&self, (width, height): (u32, u32), block_width: u32, block_height: u32) -> Self {
+        let values = (0..width * height)
+            .into_par_iter()
+            .map(|idx| {
+                let (x, y) = (idx % width / block_width, idx / width / block_height);
+
+                self.values[(y * self.width + x) as usize]
+            })
+            .collect();
+
+        Self { width, height, values }
+    }
+
+    fn transpose(&self) -> Self {
+        let values = (0..self.width * self.height)
+            .into_par_iter()
+            .map(|idx| {
+                let (x, y) = (idx / self.height, idx % self.height);
+
+                self.values[(y * self.width + x) as usize]
+            })
+            .collect();
+
+        Self { width: self.height, height: self.width, values }
+    }
+
+    /// Runs the box blurs over every row, in parallel.
+    ///
+    /// The blurring is done in fixed point so that the running sums are exact, which means each value
+    /// only depends on the pixels under the box and not on where the row started. Otherwise bands of
+    /// an image wouldn't quite line up with each other.
+    fn blur_rows(&mut self, boxes: &[usize]) {
+        const ONE: f32 = (1 << 24) as f32;
+
+        if self.width == 0 {
+            return;
+        }
+
+        self.values.par_chunks_mut(self.width as usize).for_each(|row| {
+            let mut fixed: Vec<i32> = row.iter().map(|value| (value * ONE).round() as i32).collect();
+            let mut scratch = fixed.clone();
+
+            for radius in boxes {
+                box_blur(&fixed, &mut scratch, *radius);
+                std::mem::swap(&mut fixed, &mut scratch);
+            }
+
+            for (value, fixed) in row.iter_mut().zip(fixed) {
+                *value = fixed as f32 / ONE;
+            }
+        });
+    }
+
+    /// Approximates a Gaussian blur with three box blurs in each direction, which costs the same no
+    /// matter how big sigma is. The two directions can have different sigmas.
+    pub fn blur(&self, sigma_x: f32, sigma_y: f32) -> Self {
+        let mut blurred = self.clone();
+
+        blurred.blur_rows(&boxes_for_gaussian(sigma_x));
+
+        // Columns are turned into rows so that they can be blurred in the same (cache friendly) way
+        let mut blurred = blurred.transpose();
+
+        blurred.blur_rows(&boxes_for_gaussian(sigma_y));
+
+        blurred.transpose()
+    }
+}
+
+/// The radii of three box blurs that add up to roughly a Gaussian blur with the given sigma.
+/// See <http://www.peterkovesi.com/papers/FastGaussianSmoothing.pdf>.
+fn boxes_for_gaussian(sigma: f32) -> [usize; 3] {
+    const PASSES: f32 = 3.0;
+
+    if sigma <= 0.0 {
+        return [0; 3];
+    }
+
+    let ideal_width = (12.0 * sigma * sigma / PASSES + 1.0).sqrt();
+
+    let mut lower = ideal_width.floor() as i32;
+
+    if lower % 2 == 0 {
+        lower -= 1;
+    }
+
+    let upper = lower + 2;
+    let lower_f = lower as f32;
+
+    let ideal_lower_count = (12.0 * sigma * sigma - PASSES * lower_f * lower_f - 4.0 * PASSES * lower_f - 3.0 * PASSES) / (-4.0 * lower_f - 4.0);
+    let lower_count = ideal_lower_count.round() as i32;
+
+    [0, 1, 2].map(|pass| (if pass < lower_count { lower } else { upper }).max(1) as usize / 2)
+}
+
+/// A box blur with the given radius, using a running sum. Pixels past the ends are treated as
+/// copies of the pixels at the ends.
+fn box_blur(source: &[i32], destination: &mut [i32], radius: usize) {
+    let len = source.len() as isize;
+    let radius = radius as isize;
+
+    let at = |idx: isize| source[idx.clamp(0, len - 1) as usize] as i64;
+
+    let mut sum: i64 = (-radius..=radius).map(at).sum();
+    let count = 2 * radius as i64 + 1;
+
+    for (idx, value) in destination.iter_mut().enumerate() {
+        let idx = idx as isize;
+
+        *value = ((sum + count / 2) / count) as i32;
+        sum += at(idx + radius + 1) - at(idx - radius);
+    }
+}
+
+/// How many rows above and below a band of the image have to be included for the edges inside the
+/// band to come out the same as they would if the whole image was done at once. This is as far as
+/// the box blurs can reach.
+pub fn overlap((sigma, scalar, resolution): (f32, f32, EdgeResolution), sample_height: u32) -> u32 {
+    let reach = |sigma: f32| boxes_for_gaussian(sigma).iter().sum::<usize>() as u32;
+
+    match resolution {
+        EdgeResolution::Full => reach(sigma).max(reach(sigma * scalar)),
+        EdgeResolution::Cell => {
+            let sigma = sigma / sample_height as f32;
+
+            reach(sigma).max(reach(sigma * scalar)) * sample_height
+        }
+    }
+}
+
+/// Blurs the luma of some RGBA pixels (`width`×`height`) twice and takes the difference, which is
+/// bright around edges and dark everywhere else. The result is sRGB encoded, one byte per pixel.
+///
+/// With `EdgeResolution::Cell`, all of this happens on a version of the image that has been shrunk
+/// down to one pixel per `sample_width`×`sample_height` sample (with sigma shrunk to match), and the
+/// result is stretched back out to the size of the image afterwards.
+pub fn difference_of_gaussians(
+    pixels: &[u8],
+    (width, height): (u32, u32),
+    (sigma, scalar, resolution): (f32, f32, EdgeResolution),
+    (sample_width, sample_height): (u32, u32),
+    (blending, luma): (Blending, Luma),
+) -> Vec<u8> {
+    let luma = Plane::luma(pixels, width, (blending, luma));
+
+    let (luma, sigma_x, sigma_y) = match resolution {
+        EdgeResolution::Full => (luma, sigma, sigma),
+        EdgeResolution::Cell => (
+            luma.downscale(sample_width, sample_height),
+            sigma / sample_width as f32,
+            sigma / sample_height as f32,
+        ),
+    };
+
+    let narrow = luma.blur(sigma_x, sigma_y);
+    let wide = luma.blur(sigma_x * scalar, sigma_y * scalar);
+
+    let edges = Plane {
+        values: narrow.values.par_iter().zip(&wide.values).map(|(narrow, wide)| (narrow - wide).abs() * 3.0).collect(),
+        ..luma
+    };
+
+    let edges = match resolution {
+        EdgeResolution::Full => edges,
+        EdgeResolution::Cell => edges.upscale((width, height), sample_width, sample_height),
+    };
+
+    edges
+        .values
+        .into_par_iter()
+        .map(|value| match blending {
+            Blending::Srgb => (value * 255.0).round().min(255.0) as u8,
+            Blending::Linear => linear::linear_to_srgb(value),
+        })
+        .collect()
+}
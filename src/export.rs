@@ -0,0 +1,425 @@
+use std::io::Write;
+use std::path::Path;
+use clap::ValueEnum;
+use image::{Rgba, RgbaImage};
+use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+use crate::adaptive::SubCell;
+use crate::colors::{Colour, Rgb24};
+use crate::cp437;
+use crate::grid::{Cell, Grid};
+use crate::sauce::{Kind, Sauce};
+use crate::sixel;
+use crate::luma::Luma;
+use crate::theme::{BuiltinTheme, Theme};
+use crate::{ColourDepth, Ramp};
+
+/// What the output is written out as.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Text with escape codes for the colours, for the terminal
+    Ansi,
+    /// A web page
+    Html,
+    /// A vector image, with the characters as text
+    Svg,
+    /// An image, with the characters drawn as blocks of colour
+    Png,
+    /// A DOS `.ans` file, in CP437 and the 16 VGA colours, with a SAUCE record
+    Ans,
+    /// An XBin `.xb` file, which brings its own palette of 16 colours, with a SAUCE record
+    Xbin,
+    /// mIRC colour codes, with `--irc-colours` colours
+    Irc,
+    /// A Discord ```ansi code block, which only has 8 foreground and 8 background colours
+    Discord,
+    /// `[color]` tags, for forums
+    Bbcode,
+    /// An asciinema recording, which shows the characters as they would be in the terminal
+    Cast,
+    /// The characters and their colours, row by row
+    Json,
+}
+
+impl Format {
+    /// The extension files in this format get.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Ansi | Format::Irc | Format::Discord | Format::Bbcode => "txt",
+            Format::Html => "html",
+            Format::Svg => "svg",
+            Format::Png => "png",
+            Format::Ans => "ans",
+            Format::Xbin => "xb",
+            Format::Cast => "cast",
+            Format::Json => "json",
+        }
+    }
+
+    /// The format for a file, going by its extension. `.txt` files get the same text and escape codes
+    /// as the terminal would.
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "txt" => Some(Format::Ansi),
+            "html" | "htm" => Some(Format::Html),
+            "svg" => Some(Format::Svg),
+            "png" => Some(Format::Png),
+            "ans" => Some(Format::Ans),
+            "xb" => Some(Format::Xbin),
+            "cast" => Some(Format::Cast),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// How wide DOS's screen was. `.ans` files narrower than this need line breaks, and ones at least
+/// this wide rely on the viewer wrapping them at the width SAUCE gives.
+const DOS_COLUMNS: usize = 80;
+
+/// The shade blocks (in CP437) for a cell that's none, a quarter, half, three quarters and all ink.
+const SHADES: [u8; 5] = [b' ', 0xb0, 0xb1, 0xb2, 0xdb];
+
+/// How much of a character cell the densest characters in the edge ramps (`@` and `▓`) cover, roughly.
+const MOST_INK: f32 = 0.5;
+
+/// The colours of a cell, with the defaults filled in.
+fn colours(cell: &Cell, (foreground, background): (Rgb24, Rgb24)) -> (Rgb24, Rgb24) {
+    (cell.foreground.unwrap_or(foreground), cell.background.unwrap_or(background))
+}
+
+fn hex(colour: Rgb24) -> String {
+    format!("#{:02x}{:02x}{:02x}", colour.r, colour.g, colour.b)
+}
+
+/// Escapes the characters that mean something in HTML and XML.
+fn escape(character: char) -> String {
+    match character {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        _ => character.to_string(),
+    }
+}
+
+/// Splits a row into runs of cells with the same colours, as (first column, cells).
+fn runs(row: &[Cell], defaults: (Rgb24, Rgb24)) -> Vec<(usize, &[Cell])> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+
+    for x in 1..=row.len() {
+        if x == row.len() || colours(&row[x], defaults) != colours(&row[start], defaults) {
+            runs.push((start, &row[start..x]));
+            start = x;
+        }
+    }
+
+    runs
+}
+
+/// Writes the grid out as text with escape codes, the same way apixels draws images, with the
+/// colours brought down to `depth`.
+pub fn ansi(grid: &Grid, (depth, grey_levels, luma): (ColourDepth, u16, Luma), theme: Option<&Theme>, output: &mut impl Write) -> std::io::Result<()> {
+    let quantise = |colour: Option<Rgb24>| colour.and_then(|colour| depth.quantise(colour.into_rgb(), (grey_levels, luma), theme));
+
+    for y in 0..grid.height {
+        for (x, cell) in grid.row(y).iter().enumerate() {
+            let end = if x == grid.width - 1 {
+                "\n"
+            } else if depth == ColourDepth::None {
+                ""
+            } else {
+                "\x00"
+            };
+
+            let character = cell.character;
+
+            match (quantise(cell.foreground), quantise(cell.background)) {
+                (Some(foreground), Some(background)) => write!(output, "{}{end}", OwoColorize::on_color(&character.color(foreground), background))?,
+                (Some(foreground), None) => write!(output, "{}{end}", character.color(foreground))?,
+                (None, Some(background)) => write!(output, "{}{end}", character.on_color(background))?,
+                (None, None) => write!(output, "{character}{end}")?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the grid out as a web page, with a `<span>` for each run of characters in the same colours.
+/// `defaults` are the foreground and background for cells that don't have their own.
+pub fn html(grid: &Grid, defaults: (Rgb24, Rgb24), output: &mut impl Write) -> std::io::Result<()> {
+    let (foreground, background) = defaults;
+
+    writeln!(output, "<!DOCTYPE html>")?;
+    writeln!(output, "<html><head><meta charset=\"utf-8\"></head>")?;
+    writeln!(output, "<body style=\"margin: 0; background-color: {}\">", hex(background))?;
+    write!(output, "<pre style=\"margin: 0; font-family: monospace; line-height: 1; color: {}\">", hex(foreground))?;
+
+    for y in 0..grid.height {
+        for (_, run) in runs(grid.row(y), defaults) {
+            let (foreground, background) = colours(&run[0], defaults);
+            let text: String = run.iter().map(|cell| escape(cell.character)).collect();
+
+            write!(output, "<span style=\"color: {}; background-color: {}\">{text}</span>", hex(foreground), hex(background))?;
+        }
+
+        writeln!(output)?;
+    }
+
+    writeln!(output, "</pre>")?;
+    writeln!(output, "</body></html>")
+}
+
+/// Writes the grid out as an SVG, with each character `cell_size` pixels big. Backgrounds are
+/// rectangles, and each run of characters in the same colour is one `<text>`, squashed or stretched
+/// to fit its cells exactly.
+pub fn svg(grid: &Grid, defaults: (Rgb24, Rgb24), (cell_width, cell_height): (u32, u32), output: &mut impl Write) -> std::io::Result<()> {
+    let (width, height) = (grid.width as u32 * cell_width, grid.height as u32 * cell_height);
+
+    writeln!(output, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\" xml:space=\"preserve\">")?;
+    writeln!(output, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", hex(defaults.1))?;
+    writeln!(output, "<g font-family=\"monospace\" font-size=\"{cell_height}\">")?;
+
+    for y in 0..grid.height {
+        let top = y as u32 * cell_height;
+        // Where the text sits, so that the descenders fit in the cell
+        let baseline = top + cell_height * 4 / 5;
+
+        for (x, run) in runs(grid.row(y), defaults) {
+            let (foreground, background) = colours(&run[0], defaults);
+            let (left, run_width) = (x as u32 * cell_width, run.len() as u32 * cell_width);
+
+            if background != defaults.1 {
+                writeln!(output, "<rect x=\"{left}\" y=\"{top}\" width=\"{run_width}\" height=\"{cell_height}\" fill=\"{}\"/>", hex(background))?;
+            }
+
+            if run.iter().any(|cell| !cell.character.is_whitespace()) {
+                let text: String = run.iter().map(|cell| escape(cell.character)).collect();
+
+                writeln!(output, "<text x=\"{left}\" y=\"{baseline}\" textLength=\"{run_width}\" lengthAdjust=\"spacingAndGlyphs\" fill=\"{}\">{text}</text>", hex(foreground))?;
+            }
+        }
+    }
+
+    writeln!(output, "</g>")?;
+    writeln!(output, "</svg>")
+}
+
+/// Draws the grid as an image, with each character `cell_size` pixels big. There isn't a font to
+/// draw them with, so block elements, shades and braille are drawn as blocks, and everything else
+/// as a flat mix of the two colours, going by how much ink the character has.
+pub fn rasterise(grid: &Grid, defaults: (Rgb24, Rgb24), (cell_width, cell_height): (u32, u32)) -> RgbaImage {
+    let mut img = RgbaImage::new(grid.width as u32 * cell_width, grid.height as u32 * cell_height);
+
+    for (index, cell) in grid.cells.iter().enumerate() {
+        let (foreground, background) = colours(cell, defaults);
+        let (left, top) = ((index % grid.width) as u32 * cell_width, (index / grid.width) as u32 * cell_height);
+
+        let sub_cell = [SubCell::Quadrant, SubCell::Braille]
+            .into_iter()
+            .find_map(|sub_cell| sub_cell.mask(cell.character).map(|mask| (sub_cell, mask)));
+
+        let flat = match cell.character {
+            '░' => 0.25,
+            '▒' => 0.5,
+            '▓' => 0.75,
+            character => [Ramp::Ascii, Ramp::Cp437]
+                .into_iter()
+                .find_map(|ramp| ramp.level(character))
+                .map_or(MOST_INK / 2.0, |level| level * MOST_INK),
+        };
+
+        for y in 0..cell_height {
+            for x in 0..cell_width {
+                let ink = match sub_cell {
+                    Some((sub_cell, mask)) => {
+                        let (columns, rows) = sub_cell.grid();
+                        let part = (y * rows / cell_height) * columns + x * columns / cell_width;
+
+                        (mask >> part & 1) as f32
+                    }
+                    None => flat,
+                };
+
+                let mix = |foreground: u8, background: u8| (background as f32 + (foreground as f32 - background as f32) * ink).round() as u8;
+
+                img.put_pixel(left + x, top + y, Rgba([
+                    mix(foreground.r, background.r),
+                    mix(foreground.g, background.g),
+                    mix(foreground.b, background.b),
+                    255,
+                ]));
+            }
+        }
+    }
+
+    img
+}
+
+/// The CP437 byte for the character. Quadrants and braille that CP437 doesn't have become the shade
+/// block with about as much ink.
+fn dos_character(character: char) -> u8 {
+    cp437::encode(character)
+        .or_else(|| {
+            [SubCell::Quadrant, SubCell::Braille].into_iter().find_map(|sub_cell| {
+                let (columns, rows) = sub_cell.grid();
+
+                sub_cell
+                    .mask(character)
+                    .map(|mask| SHADES[((mask.count_ones() * 4 + columns * rows / 2) / (columns * rows)) as usize])
+            })
+        })
+        .unwrap_or(b'?')
+}
+
+/// The index in `palette` of the closest colour (in CIELAB).
+pub fn nearest(palette: &[Rgb24], colour: Rgb24) -> u8 {
+    palette
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| (index, entry.colour_distance(colour)))
+        .fold((0, f64::INFINITY), |nearest, current| if current.1 < nearest.1 { current } else { nearest })
+        .0 as u8
+}
+
+/// Each cell's foreground and background as indices into the 16 colour `palette`. Without iCE
+/// colours, the bright bit of the background makes the character blink instead, so backgrounds
+/// only get the first 8.
+fn attributes(grid: &Grid, defaults: (Rgb24, Rgb24), palette: &[Rgb24], ice: bool) -> Vec<(u8, u8)> {
+    let backgrounds = if ice { palette } else { &palette[..8] };
+
+    grid.cells
+        .iter()
+        .map(|cell| {
+            let (foreground, background) = colours(cell, defaults);
+
+            (nearest(palette, foreground), nearest(backgrounds, background))
+        })
+        .collect()
+}
+
+/// Writes the grid out as a DOS `.ans` file, in CP437 and the VGA palette, with a SAUCE record on the
+/// end. With `ice`, bright backgrounds are allowed (and SAUCE says so, for the viewers that listen).
+pub fn ans(grid: &Grid, defaults: (Rgb24, Rgb24), ice: bool, sauce: &Sauce, output: &mut impl Write) -> std::io::Result<()> {
+    let vga = BuiltinTheme::Vga.theme().ansi;
+    let attributes = attributes(grid, defaults, &vga, ice);
+
+    let mut art = Vec::new();
+    let mut last = None;
+
+    for y in 0..grid.height {
+        for x in 0..grid.width {
+            let (foreground, background) = attributes[y * grid.width + x];
+
+            // Every change starts from scratch, since there's no turning bold or blink off again in ANSI.SYS
+            if last != Some((foreground, background)) {
+                art.extend(b"\x1b[0");
+
+                if foreground >= 8 {
+                    art.extend(b";1");
+                }
+
+                if background >= 8 {
+                    art.extend(b";5");
+                }
+
+                write!(art, ";{};{}m", 30 + foreground % 8, 40 + background % 8)?;
+                last = Some((foreground, background));
+            }
+
+            art.push(dos_character(grid.row(y)[x].character));
+        }
+
+        if grid.width < DOS_COLUMNS {
+            art.extend(b"\r\n");
+        }
+    }
+
+    art.extend(b"\x1b[0m");
+
+    // Anything taller than SAUCE can say just says as much as it can
+    let kind = Kind::Ansi {
+        width: u16::try_from(grid.width.max(DOS_COLUMNS)).unwrap_or(u16::MAX),
+        height: u16::try_from(grid.height).unwrap_or(u16::MAX),
+        ice,
+    };
+
+    output.write_all(&art)?;
+    sauce.write(kind, art.len(), output)
+}
+
+/// Writes the grid out as an XBin `.xb` file, with a palette of the 16 colours that suit it best
+/// and a SAUCE record on the end. Without `ice`, backgrounds only get the first 8 of them.
+pub fn xbin(grid: &Grid, defaults: (Rgb24, Rgb24), ice: bool, sauce: &Sauce, output: &mut impl Write) -> std::io::Result<()> {
+    let (Ok(width), Ok(height)) = (u16::try_from(grid.width), u16::try_from(grid.height)) else {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "XBin art can't be more than 65535 characters wide or high"));
+    };
+
+    let used: Vec<[u8; 3]> = grid.cells
+        .iter()
+        .flat_map(|cell| {
+            let (foreground, background) = colours(cell, defaults);
+
+            [foreground, background].map(|colour| [colour.r, colour.g, colour.b])
+        })
+        .collect();
+
+    let mut palette: Vec<Rgb24> = sixel::palette(&used, 16).into_iter().map(|[r, g, b]| Rgb24::from((r, g, b))).collect();
+    palette.resize(16, Rgb24::from((0, 0, 0)));
+
+    let attributes = attributes(grid, defaults, &palette, ice);
+
+    let mut art = Vec::new();
+
+    // The flags say there's a palette, and whether the blink bit means bright backgrounds. The font
+    // is 16 pixels high, and it's the one built into the viewer.
+    art.extend(b"XBIN\x1a");
+    art.extend(width.to_le_bytes());
+    art.extend(height.to_le_bytes());
+    art.extend([16, 1 | (ice as u8) << 3]);
+
+    // The palette has 6 bits for each channel, like the VGA's did
+    art.extend(palette.iter().flat_map(|colour| [colour.r >> 2, colour.g >> 2, colour.b >> 2]));
+
+    for (cell, (foreground, background)) in grid.cells.iter().zip(attributes) {
+        art.extend([dos_character(cell.character), background << 4 | foreground]);
+    }
+
+    output.write_all(&art)?;
+    sauce.write(Kind::Xbin { width, height }, art.len(), output)
+}
+
+/// Writes ANSI text out as an asciinema recording (version 2), with everything showing up at once.
+/// The recording is as big as the grid, so that nothing wraps.
+pub fn cast(text: &str, grid: &Grid, output: &mut impl Write) -> std::io::Result<()> {
+    // Terminals need a carriage return as well, and neither the NULs apixels puts between characters
+    // nor a SAUCE record should be in there
+    let text = text.split('\x1a').next().unwrap_or_default().replace('\0', "").replace('\n', "\r\n");
+
+    writeln!(output, "{}", json!({ "version": 2, "width": grid.width, "height": grid.height }))?;
+    writeln!(output, "{}", json!([0.0, "o", text]))
+}
+
+/// Writes the grid out as JSON: its size, and its rows of cells, each with a character and
+/// colours (as `#rrggbb`, or `null` for the default).
+pub fn json(grid: &Grid, output: &mut impl Write) -> std::io::Result<()> {
+    let rows: Vec<Value> = (0..grid.height)
+        .map(|y| {
+            grid.row(y)
+                .iter()
+                .map(|cell| json!({
+                    "character": cell.character.to_string(),
+                    "foreground": cell.foreground.map(hex),
+                    "background": cell.background.map(hex),
+                }))
+                .collect()
+        })
+        .collect();
+
+    serde_json::to_writer(&mut *output, &json!({ "width": grid.width, "height": grid.height, "rows": rows }))?;
+    writeln!(output)
+}
@@ -0,0 +1,264 @@
+use std::iter::Peekable;
+use std::str::Chars;
+use crate::colors::{Ansi256, Colour, Rgb24};
+use crate::theme::Theme;
+
+/// One character of ANSI art. A colour of `None` means the terminal's default.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub character: char,
+    pub foreground: Option<Rgb24>,
+    pub background: Option<Rgb24>,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            character: ' ',
+            foreground: None,
+            background: None,
+        }
+    }
+}
+
+/// How far right the cursor can go when nothing says how wide the art is. It's a long way past any
+/// real terminal, and it stops a cursor movement in a file from asking for a gigantic grid.
+const MAX_COLUMNS: usize = 4096;
+
+/// How far down the cursor can go, for the same reason. Anything drawn further down ends up on the
+/// last row.
+const MAX_ROWS: usize = 16384;
+
+/// ANSI art that's been read back into rows of characters, so it can be drawn some other way.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    /// Row by row, `width` cells to each row
+    pub cells: Vec<Cell>,
+}
+
+/// A colour as it was given in the escape code. The 16 colour palette isn't looked up until the
+/// character is drawn, since bold makes the first 8 of them brighter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Ink {
+    Index(u8),
+    Rgb(Rgb24),
+}
+
+/// Everything the SGR codes have set so far.
+#[derive(Copy, Clone, Debug, Default)]
+struct Pen {
+    foreground: Option<Ink>,
+    background: Option<Ink>,
+    bold: bool,
+    reverse: bool,
+}
+
+impl Grid {
+    /// A grid of spaces in the default colours.
+    pub fn blank(width: usize, height: usize) -> Grid {
+        Grid {
+            width,
+            height,
+            cells: vec![Cell::default(); width * height],
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[Cell] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    /// Copies `other` on top of this grid, with its top left corner at `(x, y)`. Anything that
+    /// doesn't fit is cut off.
+    pub fn paste(&mut self, other: &Grid, (x, y): (usize, usize)) {
+        for row in 0..other.height.min(self.height.saturating_sub(y)) {
+            let width = other.width.min(self.width.saturating_sub(x));
+            let start = (y + row) * self.width + x;
+
+            self.cells[start..start + width].copy_from_slice(&other.row(row)[..width]);
+        }
+    }
+
+    /// Reads text coloured with escape codes: apixels' own output, or anything else that uses SGR codes
+    /// for its colours (16, 256 or 24 bit). The 16 colours are looked up in `theme`.
+    ///
+    /// `.ans` files also move the cursor around, and rely on the terminal wrapping lines at some width
+    /// (80 columns, nearly always), which `wrap` is for. Anything after a `^Z` (which is where a SAUCE
+    /// record would be) is ignored, and so are escape codes that don't change the colours or move
+    /// the cursor. The cursor stops at the right margin like it would in a terminal, and the grid
+    /// never gets bigger than `MAX_COLUMNS` × `MAX_ROWS`, whatever the text asks for.
+    pub fn parse(text: &str, theme: &Theme, wrap: Option<usize>) -> Grid {
+        let mut rows: Vec<Vec<Cell>> = Vec::new();
+        let mut pen = Pen::default();
+        let (mut x, mut y) = (0usize, 0usize);
+        let mut saved = (0, 0);
+
+        let margin = wrap.unwrap_or(MAX_COLUMNS).clamp(1, MAX_COLUMNS);
+
+        let mut chars = text.chars().peekable();
+
+        while let Some(character) = chars.next() {
+            match character {
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        let (parameters, command) = control_sequence(&mut chars);
+
+                        let count = parameters.first().copied().flatten().unwrap_or(1).max(1) as usize;
+
+                        match command {
+                            Some('m') => pen.apply(&parameters),
+                            Some('A') => y = y.saturating_sub(count),
+                            Some('B') => y += count,
+                            Some('C') => x = (x + count).min(margin - 1),
+                            Some('D') => x = x.saturating_sub(count),
+                            Some('H' | 'f') => {
+                                let position = |index: usize| parameters.get(index).copied().flatten().unwrap_or(1).max(1) as usize - 1;
+
+                                (y, x) = (position(0), position(1).min(margin - 1));
+                            }
+                            Some('s') => saved = (x, y),
+                            Some('u') => (x, y) = saved,
+                            _ => {}
+                        }
+
+                        y = y.min(MAX_ROWS - 1);
+                    }
+                    // OSC, DCS, APC and PM strings (like sixels and Kitty's images) go on until BEL or ST
+                    Some(']' | 'P' | '_' | '^') => {
+                        while let Some(character) = chars.next() {
+                            if character == '\x07' || (character == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                                break;
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                '\n' => (x, y) = (0, (y + 1).min(MAX_ROWS - 1)),
+                '\r' => x = 0,
+                '\t' => x = ((x / 8 + 1) * 8).min(margin - 1),
+                '\x1a' => break,
+                // Including the NULs apixels puts between characters
+                _ if character.is_control() => {}
+                _ => {
+                    if x >= margin {
+                        // Without a width to wrap at, everything past the edge lands on the last column
+                        (x, y) = if wrap.is_some() { (0, (y + 1).min(MAX_ROWS - 1)) } else { (margin - 1, y) };
+                    }
+
+                    if rows.len() <= y {
+                        rows.resize(y + 1, Vec::new());
+                    }
+
+                    if rows[y].len() <= x {
+                        rows[y].resize(x + 1, Cell::default());
+                    }
+
+                    rows[y][x] = pen.cell(character, theme);
+                    x += 1;
+                }
+            }
+        }
+
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+
+        Grid {
+            width,
+            height: rows.len(),
+            cells: rows
+                .into_iter()
+                .flat_map(|mut row| {
+                    row.resize(width, Cell::default());
+                    row
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Reads the rest of a control sequence (after the `ESC [`), returning its numbers and the command.
+/// Numbers that were left out are `None`.
+fn control_sequence(chars: &mut Peekable<Chars>) -> (Vec<Option<u32>>, Option<char>) {
+    let mut text = String::new();
+
+    for character in chars.by_ref() {
+        if ('\x40'..='\x7e').contains(&character) {
+            // Private sequences (like `ESC [ ? 25 l`) aren't about colours or the cursor's position
+            if text.starts_with(['?', '<', '=', '>']) {
+                return (Vec::new(), None);
+            }
+
+            let parameters = text.split([';', ':']).map(|parameter| parameter.parse().ok()).collect();
+
+            return (parameters, Some(character));
+        }
+
+        text.push(character);
+    }
+
+    (Vec::new(), None)
+}
+
+impl Pen {
+    /// Applies an SGR code.
+    fn apply(&mut self, parameters: &[Option<u32>]) {
+        let mut parameters = parameters.iter().map(|parameter| parameter.unwrap_or(0));
+
+        while let Some(parameter) = parameters.next() {
+            match parameter {
+                0 => *self = Pen::default(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                7 => self.reverse = true,
+                27 => self.reverse = false,
+                30..=37 => self.foreground = Some(Ink::Index((parameter - 30) as u8)),
+                90..=97 => self.foreground = Some(Ink::Index((parameter - 90 + 8) as u8)),
+                40..=47 => self.background = Some(Ink::Index((parameter - 40) as u8)),
+                100..=107 => self.background = Some(Ink::Index((parameter - 100 + 8) as u8)),
+                38 => self.foreground = extended_colour(&mut parameters).or(self.foreground),
+                48 => self.background = extended_colour(&mut parameters).or(self.background),
+                39 => self.foreground = None,
+                49 => self.background = None,
+                _ => {}
+            }
+        }
+    }
+
+    /// The character, in the pen's colours.
+    fn cell(&self, character: char, theme: &Theme) -> Cell {
+        let colour = |ink: Option<Ink>, bright: bool| ink.map(|ink| match ink {
+            Ink::Index(index) if bright && index < 8 => theme.ansi[index as usize + 8],
+            Ink::Index(index) if index < 16 => theme.ansi[index as usize],
+            Ink::Index(index) => Rgb24::from(Ansi256 { index }.into_rgb()),
+            Ink::Rgb(colour) => colour,
+        });
+
+        // Bold text was drawn in the bright colours, and that's what ANSI art expects
+        let (foreground, background) = (colour(self.foreground, self.bold), colour(self.background, false));
+
+        let (foreground, background) = if self.reverse {
+            (background.or(theme.background), foreground.or(Some(theme.ansi[7])))
+        } else {
+            (foreground, background)
+        };
+
+        Cell {
+            character,
+            foreground,
+            background,
+        }
+    }
+}
+
+/// Reads the colour after a 38 or 48: either `5;index` or `2;r;g;b`.
+fn extended_colour(parameters: &mut impl Iterator<Item = u32>) -> Option<Ink> {
+    match parameters.next()? {
+        5 => Some(Ink::Index(parameters.next()?.min(255) as u8)),
+        2 => {
+            let mut channel = || parameters.next().map(|channel| channel.min(255) as u8);
+
+            Some(Ink::Rgb(Rgb24::from((channel()?, channel()?, channel()?))))
+        }
+        _ => None,
+    }
+}
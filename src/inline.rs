@@ -0,0 +1,65 @@
+use std::io::{Cursor, Write};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{ImageOutputFormat, RgbaImage};
+use crate::colors::Rgb24;
+use crate::dominant::{self, AlphaMode};
+
+/// How much of the base64 Kitty takes in each escape code. Anything bigger has to be split up.
+const KITTY_CHUNK: usize = 4096;
+
+/// Encodes the image as a PNG, for the protocols that take whole image files. With `Composite`, every
+/// pixel gets blended onto `matte` first, and otherwise the terminal does the blending itself.
+pub fn png(img: &RgbaImage, (alpha, matte): (AlphaMode, Rgb24)) -> anyhow::Result<Vec<u8>> {
+    let composited;
+
+    let img = if alpha == AlphaMode::Composite {
+        let opaque = dominant::flatten(img.as_raw(), AlphaMode::Composite, matte)
+            .into_iter()
+            .flat_map(|[r, g, b]| [r, g, b, 255])
+            .collect();
+
+        composited = RgbaImage::from_raw(img.width(), img.height(), opaque).expect("flattening keeps every pixel");
+        &composited
+    } else {
+        img
+    };
+
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+
+    Ok(png)
+}
+
+/// Writes a PNG out with the Kitty graphics protocol, stretched over `columns` × `rows` characters.
+/// `q=2` stops Kitty from answering, since nothing would read the answer.
+pub fn kitty(png: &[u8], (columns, rows): (u32, u32), output: &mut impl Write) -> std::io::Result<()> {
+    let data = STANDARD.encode(png);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        // Every chunk but the last says there's more to come
+        let more = (index + 1 < chunks.len()) as u8;
+
+        if index == 0 {
+            write!(output, "\x1b_Ga=T,f=100,q=2,c={columns},r={rows},m={more};")?;
+        } else {
+            write!(output, "\x1b_Gm={more};")?;
+        }
+
+        output.write_all(chunk)?;
+        output.write_all(b"\x1b\\")?;
+    }
+
+    Ok(())
+}
+
+/// Writes a PNG out with iTerm2's inline image protocol, stretched over `columns` × `rows` characters.
+pub fn iterm2(png: &[u8], (columns, rows): (u32, u32), output: &mut impl Write) -> std::io::Result<()> {
+    write!(
+        output,
+        "\x1b]1337;File=inline=1;size={};width={columns};height={rows};preserveAspectRatio=0:{}\x07",
+        png.len(),
+        STANDARD.encode(png),
+    )
+}
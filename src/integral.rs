@@ -0,0 +1,71 @@
+/// A summed-area table: every entry holds the sum of everything above and to the left of it, so the
+/// sum of any rectangle can be read off with four lookups, no matter how big the rectangle is.
+///
+/// The sums are `u64`s that are allowed to wrap around. The wrapping cancels out when a rectangle's
+/// sum is worked out, so the result is still exact as long as the sum of that one rectangle fits in
+/// a `u64`, which squared 8 bit values do for any image that fits in memory.
+pub struct IntegralImage<const N: usize> {
+    width: u32,
+    height: u32,
+    sums: Vec<[u64; N]>,
+}
+
+impl<const N: usize> IntegralImage<N> {
+    /// Builds the table out of the `N` values that `values` gives for each pixel.
+    pub fn from_fn(width: u32, height: u32, mut values: impl FnMut(u32, u32) -> [u64; N]) -> Self {
+        let stride = width as usize + 1;
+
+        // The extra row and column of zeros along the top and left saves on bounds checks later
+        let mut sums = vec![[0u64; N]; stride * (height as usize + 1)];
+
+        for y in 0..height {
+            let mut row_sum = [0u64; N];
+
+            for x in 0..width {
+                let value = values(x, y);
+
+                let above = sums[y as usize * stride + x as usize + 1];
+                let entry = &mut sums[(y as usize + 1) * stride + x as usize + 1];
+
+                for idx in 0..N {
+                    row_sum[idx] = row_sum[idx].wrapping_add(value[idx]);
+                    entry[idx] = row_sum[idx].wrapping_add(above[idx]);
+                }
+            }
+        }
+
+        Self { width, height, sums }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The sums over a rectangle. Anything outside of the image is cut off.
+    pub fn sum(&self, x: u32, y: u32, width: u32, height: u32) -> [u64; N] {
+        let stride = self.width as usize + 1;
+
+        let left = x.min(self.width) as usize;
+        let top = y.min(self.height) as usize;
+        let right = x.saturating_add(width).min(self.width) as usize;
+        let bottom = y.saturating_add(height).min(self.height) as usize;
+
+        let [top_left, top_right, bottom_left, bottom_right] = [
+            self.sums[top * stride + left],
+            self.sums[top * stride + right],
+            self.sums[bottom * stride + left],
+            self.sums[bottom * stride + right],
+        ];
+
+        std::array::from_fn(|idx| {
+            bottom_right[idx]
+                .wrapping_sub(bottom_left[idx])
+                .wrapping_sub(top_right[idx])
+                .wrapping_add(top_left[idx])
+        })
+    }
+}
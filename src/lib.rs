@@ -1,25 +1,75 @@
 #![feature(int_roundings)]
 #![feature(iter_array_chunks)]
-#![feature(array_chunks)]
 #![feature(unchecked_math)]
 
+pub mod adaptive;
+pub mod batch;
+pub mod chat;
 pub mod colors;
+pub mod cp437;
 pub mod dominant;
-
-use std::io::Cursor;
-use anyhow::{anyhow, bail};
-use colors::{Ansi, Colour, Monochrome, Rgb24, Rgb565};
+pub mod edges;
+pub mod export;
+pub mod grid;
+pub mod inline;
+pub mod integral;
+pub mod linear;
+pub mod luma;
+pub mod picker;
+pub mod preprocess;
+pub mod sauce;
+pub mod sheet;
+pub mod sixel;
+pub mod terminal;
+pub mod theme;
+pub mod transform;
+
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use anyhow::bail;
+use adaptive::SubCell;
+use colors::{Ansi, Ansi256, Colour, Monochrome, Rgb24, Rgb332, Rgb444, Rgb555, Rgb565};
+use dominant::AlphaMode;
+use edges::EdgeResolution;
+use export::Format;
+use integral::IntegralImage;
+use linear::Blending;
+use luma::Luma;
+use picker::{ColourPicker, Picker};
+use preprocess::{Adjustment, Levels, Pipeline};
+use sauce::Sauce;
+use terminal::Background;
+use theme::Theme;
+use transform::{Flip, Region, Transform};
 use clap::ValueEnum;
-use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel};
-use owo_colors::OwoColorize;
+use image::imageops::FilterType;
+use image::io::Limits;
+use image::RgbaImage;
+use owo_colors::{AnsiColors, DynColors, OwoColorize};
 use rayon::prelude::*;
 use clap::clap_derive::*;
 
 #[derive(Parser)]
 pub struct Arguments {
-    /// The name of the file
-    #[arg(short, long)]
-    pub file_name: String,
+    /// The files to draw. Any more than one, or directories, or patterns like `art/*.png` (quoted, so
+    /// that apixels sees them rather than the shell), need `--output-dir` to put them in
+    #[arg(short, long, num_args = 1.., required = true)]
+    pub file_name: Vec<String>,
+
+    /// Looks for images in the subdirectories of any directories given, too
+    #[arg(short = 'R', long, default_value_t = false)]
+    pub recursive: bool,
+
+    /// Converts every file into this directory, rather than drawing one in the terminal. Files
+    /// found in subdirectories go in the same subdirectories here
+    #[arg(long)]
+    pub output_dir: Option<String>,
+
+    /// What to call each file in `--output-dir`. `{stem}` is the input's name without its
+    /// extension, `{name}` is with it, and `{ext}` is the extension for `--format`
+    #[arg(long, default_value = "{stem}.{ext}")]
+    pub name_template: String,
 
     /// The width of each sample (the pixels used to determine the colour of each character).
     /// The higher this is, the smaller the overall output will be. In this case, the higher it is,
@@ -33,15 +83,39 @@ pub struct Arguments {
     #[arg(long, default_value_t = 3)]
     pub sample_height: u32,
 
-    /// The supported colour depths. ANSI still requires truecolor support due to the nature of
-    /// the way I implemented colours in the code, and I am much too lazy to change it.
-    #[arg(short, long, value_enum, default_value_t = ColourDepth::Rgb24)]
-    pub depth: ColourDepth,
+    /// The colour depth. When it isn't given, it's worked out from what the terminal supports
+    /// (going by COLORTERM, TERM and terminfo), and output that isn't going to a terminal is plain
+    /// text. NO_COLOR and FORCE_COLOR are also respected.
+    #[arg(short, long, value_enum)]
+    pub depth: Option<ColourDepth>,
+
+    /// How many shades of grey `--depth grayscale` has to work with, black and white included
+    #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u16).range(2..=256))]
+    pub grey_levels: u16,
+
+    /// How colours are turned into shades of grey, both for `--depth grayscale` and for working out
+    /// how much edge there is in each character
+    #[arg(long, value_enum, default_value_t = Luma::Rec709)]
+    pub luma: Luma,
+
+    /// The terminal's colour scheme, so that `--depth ansi` and `--depth ansi256` pick the colours
+    /// that actually look the closest. Either the name of a built in theme (ubuntu, xterm, vga,
+    /// solarized-dark, solarized-light, gruvbox-dark, gruvbox-light, dracula or nord) or the path to an
+    /// iTerm2 `.itermcolors` file, a Windows Terminal scheme (`.json`), an alacritty config (`.toml`)
+    /// or an `.Xresources` file. Its background is used if `--background` isn't given.
+    #[arg(long)]
+    pub theme: Option<String>,
 
     /// You can choose not to have a background colour (only one colour per character)
     #[arg(short, long, default_value_t = false)]
     pub no_background: bool,
 
+    /// The colour of the terminal's background: `light`, `dark`, `#rrggbb`, or `auto` to ask the terminal.
+    /// Once it's known, characters that would blend into it are left blank, and without a background
+    /// colour each character is drawn in whichever of its two colours stands out more.
+    #[arg(long)]
+    pub background: Option<Background>,
+
     /// Apixels uses the difference of Gaussians for edge detection. This is the sigma value used in the blur
     #[arg(short, long, default_value_t = 3.0f32)]
     pub sigma: f32,
@@ -56,188 +130,751 @@ pub struct Arguments {
     /// the reckoning of man, but the eldritch horrors should also coincide with the edges of your image.
     #[arg(short, long, default_value_t = false)]
     pub edges: bool,
+
+    /// Run the edge detection on every pixel (`full`) or on a shrunk down copy of the image with one
+    /// pixel per character (`cell`). `cell` is much faster on big images, at the cost of fine detail.
+    #[arg(long, value_enum, default_value_t = EdgeResolution::Full)]
+    pub edge_resolution: EdgeResolution,
+
+    /// Roughly how much memory (in MiB) apixels is allowed to use. Images that are too big to decode
+    /// within it are turned down. Images are worked on (and written out) a band at a time, and the
+    /// bands get shorter to stay under it.
+    #[arg(long, default_value_t = 1024)]
+    pub memory_limit: u64,
+
+    /// How transparent pixels are handled. `skip` leaves them out of each sample, `composite` blends
+    /// them onto the matte colour and `empty` does the same but leaves mostly transparent cells blank
+    /// (no background colour at all), so your terminal's own background shows through.
+    #[arg(short, long, value_enum, default_value_t = AlphaMode::Empty)]
+    pub alpha: AlphaMode,
+
+    /// The colour partly transparent pixels get blended onto, as #rrggbb
+    #[arg(short, long, default_value = "#000000")]
+    pub matte: Rgb24,
+
+    /// With `--alpha empty`, cells that are less opaque than this (0 is fully transparent, 1 is fully
+    /// opaque) are left blank.
+    #[arg(long, default_value_t = 0.5f32)]
+    pub alpha_threshold: f32,
+
+    /// How the two colours of each character (background and foreground) are picked from its sample
+    #[arg(short, long, value_enum, default_value_t = Picker::ModeMean)]
+    pub picker: Picker,
+
+    /// Average and blur colours in linear light instead of on the raw (sRGB) values. Mixed colours come
+    /// out brighter and truer to the original, especially around sharp, high contrast edges.
+    #[arg(short, long, default_value_t = false)]
+    pub linear: bool,
+
+    /// Brighten (or darken, if negative) the image by this much, from -255 to 255
+    #[arg(long, allow_negative_numbers = true)]
+    pub brightness: Option<i32>,
+
+    /// Increase (or decrease, if negative) the contrast by this many percent
+    #[arg(long, allow_negative_numbers = true)]
+    pub contrast: Option<f32>,
+
+    /// Gamma correction. Above 1 brightens the midtones, below 1 darkens them
+    #[arg(long)]
+    pub gamma: Option<f32>,
+
+    /// Saturation multiplier. 0 is grayscale, 1 leaves the colours alone
+    #[arg(long)]
+    pub saturation: Option<f32>,
+
+    /// Rotate the hue of the image by this many degrees
+    #[arg(long, allow_negative_numbers = true)]
+    pub hue: Option<i32>,
+
+    /// Sharpen the image with an unsharp mask with this sigma
+    #[arg(long)]
+    pub sharpen: Option<f32>,
+
+    /// Differences smaller than this are left alone by `--sharpen`
+    #[arg(long, default_value_t = 0)]
+    pub sharpen_threshold: i32,
+
+    /// Invert the colours of the image
+    #[arg(long, default_value_t = false)]
+    pub invert: bool,
+
+    /// Automatically fix up the levels of the image
+    #[arg(long, value_enum)]
+    pub levels: Option<Levels>,
+
+    /// How many tiles across (and down) `--levels clahe` splits the image into
+    #[arg(long, default_value_t = 8)]
+    pub clahe_tiles: u32,
+
+    /// How far `--levels clahe` is allowed to boost the contrast of each tile
+    #[arg(long, default_value_t = 2.0f32)]
+    pub clahe_clip_limit: f32,
+
+    /// Only use this part of the image, as x,y,width,height. Each of those can be in pixels or a
+    /// percentage of the image, e.g. `25%,25%,50%,50%` for the middle of the image.
+    #[arg(long)]
+    pub crop: Option<Region>,
+
+    /// Rotate the image clockwise by this many degrees. Anything that isn't a multiple of 90 leaves
+    /// transparent corners, which work nicely with `--alpha empty`.
+    #[arg(long, allow_negative_numbers = true)]
+    pub rotate: Option<f32>,
+
+    /// Flip the image. Can be given more than once
+    #[arg(long, value_enum)]
+    pub flip: Vec<Flip>,
+
+    /// Experimental: spend more detail on the busy parts of the image. Cells with a lot of edges in them
+    /// are drawn with glyphs that have more than one "pixel" in them, and everything else is filled in
+    /// with flat colour. The output is the same size either way.
+    #[arg(long, value_enum)]
+    pub adaptive: Option<SubCell>,
+
+    /// How much edge (from 0 to 255) a cell needs before `--adaptive` treats it as detailed
+    #[arg(long, default_value_t = 24)]
+    pub detail_threshold: u8,
+
+    /// Show the image as actual pixels instead of characters, for terminals that can. It takes up the
+    /// same number of columns and rows as the characters would have. `auto` uses whatever the terminal
+    /// says it can do, and falls back to characters if it can't do anything.
+    #[arg(long, value_enum)]
+    pub graphics: Option<Graphics>,
+
+    /// How wide each character is in pixels, for `--graphics`. The terminal is asked if this isn't given
+    #[arg(long)]
+    pub cell_width: Option<u32>,
+
+    /// How tall each character is in pixels, for `--graphics`. The terminal is asked if this isn't given
+    #[arg(long)]
+    pub cell_height: Option<u32>,
+
+    /// What to write the output out as. Anything but `ansi` reads the characters back in and draws
+    /// them some other way, so it works with `--ansi` too. Without this, it goes by the extension
+    /// of `--output`, or it's `ansi`
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+
+    /// Writes the output to this file rather than stdout (which `-` also means)
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// The file is ANSI art (like apixels' own output, or a `.ans` file) rather than an image, to be
+    /// turned into something else with `--format`. Files ending in `.ans` always are
+    #[arg(long, default_value_t = false)]
+    pub ansi: bool,
+
+    /// The characters edges are drawn with. `--format ans` and `--format xbin` use `cp437` unless
+    /// told otherwise, and everything else uses `ascii`
+    #[arg(long, value_enum)]
+    pub ramp: Option<Ramp>,
+
+    /// Lets `--format ans` and `--format xbin` use bright backgrounds (iCE colours), which stops
+    /// anything from blinking
+    #[arg(long, default_value_t = false)]
+    pub ice: bool,
+
+    /// The title to put in the SAUCE record of `--format ans` and `--format xbin`
+    #[arg(long, default_value = "")]
+    pub title: String,
+
+    /// Who made it, for the SAUCE record
+    #[arg(long, default_value = "")]
+    pub author: String,
+
+    /// The group they made it for, for the SAUCE record
+    #[arg(long, default_value = "")]
+    pub group: String,
+
+    /// The font the art should be shown in, for the SAUCE record
+    #[arg(long, default_value = "IBM VGA")]
+    pub font: String,
+
+    /// How many colours `--format irc` gets to use: the 16 every client has, or the 99 that newer
+    /// ones do
+    #[arg(long, default_value_t = 16, value_parser = clap::builder::TypedValueParser::map(clap::builder::PossibleValuesParser::new(["16", "99"]), |colours| colours.parse::<u8>().unwrap()))]
+    pub irc_colours: u8,
+
+    /// Draws all of the files side by side on one contact sheet, this many to a row
+    #[arg(long, value_name = "COLUMNS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub sheet: Option<u32>,
+
+    /// How wide each image on a contact sheet can be, in characters. Images keep their aspect
+    /// ratio, so they only fill their slot one way
+    #[arg(long, default_value_t = 40, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tile_width: u32,
+
+    /// How high each image on a contact sheet can be, in characters
+    #[arg(long, default_value_t = 15, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tile_height: u32,
+
+    /// How many columns of space go between the images on a contact sheet
+    #[arg(long, default_value_t = 2)]
+    pub gutter: u32,
+
+    /// How many rows of space go between the rows of images on a contact sheet
+    #[arg(long, default_value_t = 1)]
+    pub gutter_rows: u32,
+
+    /// Puts each file's name under it on a contact sheet
+    #[arg(long, default_value_t = false)]
+    pub captions: bool,
+
+    /// How many colours `--graphics sixel` gets to use
+    #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u16).range(2..=256))]
+    pub sixel_colours: u16,
+}
+
+impl Arguments {
+    /// The format asked for, or the one `--output`'s extension is for, or `ansi` if neither says.
+    pub fn format(&self) -> Format {
+        self.format
+            .or_else(|| self.output_file().and_then(Format::from_extension))
+            .unwrap_or(Format::Ansi)
+    }
+
+    /// The file the output goes to, if it isn't going to stdout.
+    pub fn output_file(&self) -> Option<&Path> {
+        self.output.as_deref().filter(|output| *output != "-").map(Path::new)
+    }
+
+    /// The colour depth asked for, or the best one for the terminal if none was. Files (and pipes)
+    /// don't get any colour unless it's asked for, going by what's usual for command line tools, but
+    /// anything other than `--format ansi` isn't for the terminal in the first place, so it gets
+    /// every colour.
+    pub fn depth(&self) -> ColourDepth {
+        match self.format() {
+            Format::Ansi => self.depth.unwrap_or_else(|| terminal::detect(self.output_file().is_some() || self.output_dir.is_some())),
+            _ => self.depth.unwrap_or(ColourDepth::Rgb24),
+        }
+    }
+
+    /// Whether the file is ANSI art rather than an image: if `--ansi` says so, if it's a `.ans` file,
+    /// or if it came from stdin and isn't any kind of image.
+    pub fn is_ansi(&self, file_name: &Path, contents: &[u8]) -> bool {
+        self.ansi
+            || file_name.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ans"))
+            || (file_name == Path::new("-") && image::guess_format(contents).is_err())
+    }
+
+    /// The size of each character in pixels for `--format svg` and `--format png`, which is 8×16
+    /// unless it's been given.
+    pub fn export_cell_size(&self) -> (u32, u32) {
+        (self.cell_width.unwrap_or(8), self.cell_height.unwrap_or(16))
+    }
+
+    /// The characters to draw edges with.
+    pub fn ramp(&self) -> Ramp {
+        self.ramp.unwrap_or(match self.format() {
+            Format::Ans | Format::Xbin => Ramp::Cp437,
+            _ => Ramp::Ascii,
+        })
+    }
+
+    /// The SAUCE record for `--format ans` and `--format xbin`, dated today.
+    pub fn sauce(&self) -> Sauce {
+        Sauce {
+            title: self.title.clone(),
+            author: self.author.clone(),
+            group: self.group.clone(),
+            date: sauce::date(SystemTime::now()),
+            font: self.font.clone(),
+        }
+    }
+
+    /// The colour of the terminal's background, if it was given or the terminal could be asked.
+    pub fn background(&self) -> Option<Rgb24> {
+        self.background.and_then(Background::resolve)
+    }
+
+    /// The memory limit in bytes. Anything too big to count in bytes is as good as no limit at all.
+    pub fn memory_limit(&self) -> u64 {
+        self.memory_limit.saturating_mul(1 << 20)
+    }
+
+    /// The kind of graphics asked for, or `None` for characters.
+    pub fn graphics(&self) -> Option<Graphics> {
+        match self.graphics {
+            Some(Graphics::Auto) => terminal::detect_graphics(),
+            graphics => graphics,
+        }
+    }
+
+    /// The size of each character in pixels, going by the arguments, then the terminal, and then
+    /// falling back to 10×20, which is about what most terminals use.
+    pub fn cell_size(&self) -> (u32, u32) {
+        match (self.cell_width, self.cell_height) {
+            (Some(width), Some(height)) => (width, height),
+            (width, height) => {
+                let (detected_width, detected_height) = terminal::detect_cell_size().unwrap_or((10, 20));
+
+                (width.unwrap_or(detected_width), height.unwrap_or(detected_height))
+            }
+        }
+    }
+
+    /// The theme asked for, if any.
+    pub fn theme(&self) -> anyhow::Result<Option<Theme>> {
+        self.theme.as_deref().map(Theme::named).transpose()
+    }
+
+    /// The pre-processing asked for on the command line. The image is cropped, rotated and flipped
+    /// first, then the levels are fixed, then brightness, contrast, gamma, saturation, hue, sharpening
+    /// and inversion are applied, in that order.
+    pub fn pipeline(&self) -> Pipeline {
+        let transforms = self.crop.map(Transform::Crop)
+            .into_iter()
+            .chain(self.rotate.and_then(Transform::rotate))
+            .chain(self.flip.iter().copied().map(Transform::Flip))
+            .map(Adjustment::Transform);
+
+        let levels = self.levels.map(|levels| match levels {
+            Levels::Auto => Adjustment::AutoLevels,
+            Levels::Equalise => Adjustment::Equalise,
+            Levels::Clahe => Adjustment::Clahe { tiles: self.clahe_tiles, clip_limit: self.clahe_clip_limit },
+        });
+
+        let adjustments = [
+            levels,
+            self.brightness.map(Adjustment::Brightness),
+            self.contrast.map(Adjustment::Contrast),
+            self.gamma.map(Adjustment::Gamma),
+            self.saturation.map(Adjustment::Saturation),
+            self.hue.map(Adjustment::HueShift),
+            self.sharpen.map(|sigma| Adjustment::Sharpen { sigma, threshold: self.sharpen_threshold }),
+            self.invert.then_some(Adjustment::Invert),
+        ]
+            .into_iter()
+            .flatten();
+
+        transforms
+            .chain(adjustments)
+            .fold(Pipeline::new(), Pipeline::then)
+    }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum ColourDepth {
     Grayscale,
     Rgb24,
     Rgb16,
+    /// 15 bit colour, 5 bits per channel
+    Rgb555,
+    /// 12 bit colour, 4 bits per channel
+    Rgb444,
+    /// 8 bit colour: 3 bits of red and green, 2 of blue
+    Rgb332,
+    /// The terminal's 256 colour palette
+    Ansi256,
+    /// The terminal's 16 colour palette
     Ansi,
+    /// Plain text, without any colour
     None
 }
 
+impl ColourDepth {
+    /// The closest colour to `colour` that can be shown at this depth, or `None` if there's no colour
+    /// at all. `grey_levels` and `luma` are only used by `Grayscale`, and `theme` by `Ansi256` and `Ansi`.
+    pub fn quantise(self, colour: (u8, u8, u8), (grey_levels, luma): (u16, Luma), theme: Option<&Theme>) -> Option<DynColors> {
+        let rgb = |(r, g, b): (u8, u8, u8)| Some(DynColors::Rgb(r, g, b));
+
+        match self {
+            ColourDepth::Grayscale => rgb(Monochrome::with_levels(Rgb24::from(colour), luma, grey_levels).into_rgb()),
+            ColourDepth::Rgb24 => rgb(Rgb24::from_rgb8(colour).into_rgb()),
+            ColourDepth::Rgb16 => rgb(Rgb565::from_rgb8(colour).into_rgb()),
+            ColourDepth::Rgb555 => rgb(Rgb555::from_rgb8(colour).into_rgb()),
+            ColourDepth::Rgb444 => rgb(Rgb444::from_rgb8(colour).into_rgb()),
+            ColourDepth::Rgb332 => rgb(Rgb332::from_rgb8(colour).into_rgb()),
+            ColourDepth::Ansi256 => {
+                let ansi256 = theme.map_or_else(|| Ansi256::from_rgb8(colour), |theme| theme.nearest_ansi256(Rgb24::from(colour)));
+
+                Some(DynColors::Xterm(ansi256.index.into()))
+            }
+            ColourDepth::Ansi => {
+                let ansi = theme.map_or_else(|| Ansi::from_rgb8(colour), |theme| theme.nearest_ansi(Rgb24::from(colour)));
+
+                Some(DynColors::Ansi(SGR_COLOURS[ansi as usize]))
+            }
+            ColourDepth::None => None,
+        }
+    }
+}
+
+/// Ways of drawing actual pixels in a terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Graphics {
+    /// Whatever the terminal supports
+    Auto,
+    /// DEC sixel graphics, which xterm (with `-ti vt340`), mlterm, foot and WezTerm can all show
+    Sixel,
+    /// Kitty's graphics protocol, which Ghostty and WezTerm also understand
+    Kitty,
+    /// iTerm2's inline images, which WezTerm also understands
+    Iterm2,
+}
+
+/// The escape codes for each [`Ansi`] colour, in the same order.
+const SGR_COLOURS: [AnsiColors; 16] = [
+    AnsiColors::Black,
+    AnsiColors::Red,
+    AnsiColors::Green,
+    AnsiColors::Yellow,
+    AnsiColors::Blue,
+    AnsiColors::Magenta,
+    AnsiColors::Cyan,
+    AnsiColors::White,
+    AnsiColors::BrightBlack,
+    AnsiColors::BrightRed,
+    AnsiColors::BrightGreen,
+    AnsiColors::BrightYellow,
+    AnsiColors::BrightBlue,
+    AnsiColors::BrightMagenta,
+    AnsiColors::BrightCyan,
+    AnsiColors::BrightWhite,
+];
+
+/// Roughly how much memory each pixel of a band takes up while it's being worked on: the luma and its
+/// blurred copies, the edges and the integral images (which are 64 bytes on their own).
+const BAND_BYTES_PER_PIXEL: u64 = 96;
+
+/// The most rows of characters that are worked out before they're written out (see [`band_height`]).
+const STREAM_ROWS: u64 = 32;
+
+/// Colours closer than this (in CIELAB) to the terminal's background can't really be told apart from it.
+const BACKGROUND_DISTANCE: f64 = 8.0;
+
 const EDGE_DETAIL: [u8; 90] = *b" `-:_,^=;><+!rc*/z?sLTv)J7(|Fi{C}fI31tlu[neoZ5Yxjya]2ESwqkP6h9d4VpOGbUAKXHm8RD#$Bg0MNWQ%&@";
 
+/// The same idea as `EDGE_DETAIL`, but only with characters DOS had. The full block is left off the
+/// end, since it would hide the background colour completely.
+const CP437_DETAIL: [char; 12] = [' ', '·', '∙', '-', '─', '═', '┼', '╪', '╬', '░', '▒', '▓'];
+
+/// The characters that edges are drawn with, from the faintest to the strongest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Ramp {
+    /// Printable ASCII, by how much ink each character has
+    Ascii,
+    /// CP437's dots, box drawing and shade blocks, for ANSI art that DOS can show
+    Cp437,
+}
+
+impl Ramp {
+    fn len(self) -> usize {
+        match self {
+            Ramp::Ascii => EDGE_DETAIL.len(),
+            Ramp::Cp437 => CP437_DETAIL.len(),
+        }
+    }
+
+    fn get(self, index: usize) -> char {
+        match self {
+            Ramp::Ascii => EDGE_DETAIL[index] as char,
+            Ramp::Cp437 => CP437_DETAIL[index],
+        }
+    }
+
+    /// The character for an edge of the given strength.
+    pub fn glyph(self, edge: u8) -> char {
+        let index = (edge as f64 / 255.0) * (self.len() - 1) as f64;
+
+        self.get((index as usize).min(self.len() - 1))
+    }
+
+    /// The faintest character that isn't blank.
+    pub fn faintest(self) -> char {
+        self.get(1)
+    }
+
+    /// How far along the ramp the character is, from 0 to 1, if it's on it at all.
+    pub fn level(self, character: char) -> Option<f32> {
+        (0..self.len())
+            .find(|index| self.get(*index) == character)
+            .map(|index| index as f32 / (self.len() - 1) as f32)
+    }
+}
+
+/// Decodes the image (allocating no more than `memory_limit` bytes), turns it the right way up and
+/// runs it through the pipeline.
+pub fn decode(img_rgb8: Vec<u8>, pipeline: &Pipeline, memory_limit: u64) -> anyhow::Result<RgbaImage> {
+    // The decoder doesn't look at EXIF data, so photos that were taken sideways would come out sideways
+    let orientation = transform::orientation(&img_rgb8);
+
+    let mut reader = image::io::Reader::new(Cursor::new(img_rgb8)).with_guessed_format()?;
+
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(memory_limit);
+    reader.limits(limits);
+
+    let img = reader.decode()?;
+
+    // Sampling only understands 8 bit RGBA, so everything else gets converted up front.
+    let img = orientation.iter().fold(dominant::normalise(img), |img, transform| transform.apply(&img));
+
+    let img = pipeline.apply(img);
+
+    let image_bytes = img.as_raw().len() as u64;
+
+    if image_bytes > memory_limit {
+        bail!("The image needs {} MiB of memory once it's decoded, which is over the memory limit", image_bytes.div_ceil(1 << 20))
+    }
+
+    Ok(img)
+}
+
+/// Shows the image as `graphics` rather than as characters, taking up as many columns and rows as
+/// [`into_ascii_controlled`] would with the same sample size. `cell_size` is the size of each
+/// character in pixels.
 #[allow(clippy::too_many_arguments)]
-pub fn into_ascii_controlled(img_rgb8: Vec<u8>,
-                             (sigma, scalar): (f32, f32),
-                             (sample_width, sample_height): (u32, u32),
-                             no_background: bool,
-                             show_edges: bool,
-                             depth: ColourDepth,
-) -> anyhow::Result<String> {
+pub fn into_graphics(img_rgb8: Vec<u8>,
+                     graphics: Graphics,
+                     (sample_width, sample_height): (u32, u32),
+                     (cell_width, cell_height): (u32, u32),
+                     (alpha, matte): (AlphaMode, Rgb24),
+                     colours: u16,
+                     pipeline: &Pipeline,
+                     memory_limit: u64,
+                     output: &mut impl Write,
+) -> anyhow::Result<()> {
 
-    let img = image::io::Reader::new(Cursor::new(img_rgb8)).with_guessed_format()?.decode()?;
+    if sample_width == 0 || sample_height == 0 {
+        bail!("Sample width and height must be greater than zero!")
+    }
+
+    if cell_width == 0 || cell_height == 0 {
+        bail!("Cell width and height must be greater than zero!")
+    }
 
-    let img_blur = img.blur(scalar * sigma);
+    if !(2..=256).contains(&colours) {
+        bail!("There must be between 2 and 256 colours!")
+    }
 
-    let mut img_blur = img_blur.pixels();
+    let img = decode(img_rgb8, pipeline, memory_limit)?;
 
-    let edges: ImageBuffer<image::Rgb<u8>, _> = ImageBuffer::from_vec(
-        img.width(),
-        img.height(),
-        img.blur(sigma)
-            .pixels()
-            .flat_map(|pix| unsafe {
-                let (_, _, image::Rgba([r1, g1, b1, _])) = pix;
-                let (_, _, image::Rgba([r2, g2, b2, _])) = img_blur.next().unwrap();
+    let (columns, rows) = (img.width().div_ceil(sample_width), img.height().div_ceil(sample_height));
+    let (width, height) = (columns * cell_width, rows * cell_height);
 
-                let r = r1.checked_sub(r2).unwrap_or(r2.unchecked_sub(r1)).saturating_mul(3);
-                let g = g1.checked_sub(g2).unwrap_or(g2.unchecked_sub(g1)).saturating_mul(3);
-                let b = b1.checked_sub(b2).unwrap_or(b2.unchecked_sub(b1)).saturating_mul(3);
+    if img.as_raw().len() as u64 + width as u64 * height as u64 * 4 > memory_limit {
+        bail!("The image needs more memory than the memory limit once it's been resized to {width}x{height}")
+    }
 
-                [
-                    r,
-                    g,
-                    b,
-                ]
-            })
-            .collect(),
-    )
-        .ok_or(anyhow!("Failed to create an image buffer"))?;
+    let img = image::imageops::resize(&img, width, height, FilterType::Triangle);
 
-    let edges = DynamicImage::from(edges);
+    match graphics {
+        Graphics::Sixel => sixel::encode(&img, colours, (alpha, matte), output)?,
+        Graphics::Kitty => inline::kitty(&inline::png(&img, (alpha, matte))?, (columns, rows), output)?,
+        Graphics::Iterm2 => inline::iterm2(&inline::png(&img, (alpha, matte))?, (columns, rows), output)?,
+        Graphics::Auto => bail!("`auto` has to be swapped for whatever the terminal supports first"),
+    }
+
+    Ok(())
+}
+
+/// How many rows of pixels to work on at once, out of an image `width` pixels wide, with `budget`
+/// bytes to spare. That's as much as fits in the budget (with `overlap` rows either side), but no
+/// more than `STREAM_ROWS` rows of characters, so that the output starts to show up before the
+/// whole image is done. It's always a whole number of rows of characters, and at least one.
+pub fn band_height(budget: u64, width: u32, overlap: u32, sample_height: u32) -> u32 {
+    let affordable = (budget / (BAND_BYTES_PER_PIXEL * width.max(1) as u64)).saturating_sub(2 * overlap as u64);
+
+    // Bands much shorter than the overlap would spend most of their time blurring their neighbours' rows
+    let streaming = (STREAM_ROWS * sample_height as u64).max(8 * overlap as u64);
+
+    let rows = u32::try_from(affordable.min(streaming)).unwrap_or(u32::MAX);
+
+    (rows / sample_height).max(1) * sample_height
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn into_ascii_controlled(img_rgb8: Vec<u8>,
+                             (sigma, scalar, edge_resolution): (f32, f32, EdgeResolution),
+                             (sample_width, sample_height): (u32, u32),
+                             (no_background, background): (bool, Option<Rgb24>),
+                             show_edges: bool,
+                             ramp: Ramp,
+                             (depth, grey_levels, theme): (ColourDepth, u16, Option<&Theme>),
+                             (alpha, matte, alpha_threshold): (AlphaMode, Rgb24, f32),
+                             picker: &dyn ColourPicker,
+                             (blending, luma): (Blending, Luma),
+                             pipeline: &Pipeline,
+                             adaptive: Option<(SubCell, u8)>,
+                             memory_limit: u64,
+                             output: &mut impl Write,
+) -> anyhow::Result<()> {
+
+    if !(2..=256).contains(&grey_levels) {
+        bail!("There must be between 2 and 256 grey levels!")
+    }
+
+    if !(0.0..=1.0).contains(&alpha_threshold) {
+        bail!("The alpha threshold must be between 0 and 1!")
+    }
 
     if sample_width == 0 || sample_height == 0 {
         bail!("Sample width and height must be greater than zero!")
     }
 
+    let img = decode(img_rgb8, pipeline, memory_limit)?;
+
     let img_width = img.width();
     let img_height = img.height();
 
+    let image_bytes = img.as_raw().len() as u64;
+
+    // The image is done in bands of whole rows of characters, so that the blurs and integral images
+    // only ever need to be as big as one band (plus enough rows either side for the blurs)
+    let overlap = edges::overlap((sigma, scalar, edge_resolution), sample_height);
+
+    let band_height = band_height(memory_limit - image_bytes, img_width, overlap, sample_height);
+
     let output_text_width = img_width.div_ceil(sample_width);
-    let output_text_height = img_height.div_ceil(sample_height);
-
-    let output: String = (0..output_text_height)
-        .into_par_iter()
-        .flat_map(|y| {
-            // Get a reference to img so that it isn't moved inside the closure
-            let img = &img;
-
-            let edges = &edges;
-
-            // Closure must be `move` because it may outlive `y`.
-            (0..output_text_width).into_par_iter().map(move |x| {
-                let edge_sample = edges.crop_imm(
-                    sample_width * x,
-                    sample_height * y,
-                    sample_width,
-                    sample_height,
-                );
-
-                let sample = if !show_edges { img.crop_imm(
-                    sample_width * x,
-                    sample_height * y,
-                    sample_width,
-                    sample_height,
-                ) } else { edge_sample.clone() };
-
-                let [dominant, secondary] =
-                    dominant::two_most_dominant(sample.as_bytes()).map(|x| x.into_rgb());
-
-                // I could and should extract these two blocks out into a function. However, I do not care.
-                // If you care, you can submit a PR.
-                let (dominant_r, dominant_g, dominant_b) = match depth {
-                    ColourDepth::Grayscale => {
-                        let monochrome = Monochrome::from_rgb8(dominant);
-
-                        monochrome.into_rgb()
-                    }
-                    ColourDepth::Rgb24 => {
-                        let true_color = Rgb24::from_rgb8(dominant);
 
-                        true_color.into_rgb()
-                    }
-                    ColourDepth::Rgb16 => {
-                        let rgb16_color = Rgb565::from_rgb8(dominant);
+    for band_top in (0..img_height).step_by(band_height as usize) {
+        let band_bottom = (band_top + band_height).min(img_height);
+        let band_rows = band_bottom - band_top;
 
-                        rgb16_color.into_rgb()
-                    }
-                    ColourDepth::Ansi => {
-                        let ansi_color = Ansi::from_rgb8(dominant);
+        let rows = |top: u32, bottom: u32| &img.as_raw()[top as usize * img_width as usize * 4..bottom as usize * img_width as usize * 4];
 
-                        ansi_color.into_rgb()
-                    }
-                    ColourDepth::None => (0, 0, 0)
-                };
+        let (edges_top, edges_bottom) = (band_top.saturating_sub(overlap), (band_bottom + overlap).min(img_height));
 
-                let (secondary_r, secondary_g, secondary_b) = match depth {
-                    ColourDepth::Grayscale => {
-                        let monochrome = Monochrome::from_rgb8(secondary);
+        let edges = edges::difference_of_gaussians(
+            rows(edges_top, edges_bottom),
+            (img_width, edges_bottom - edges_top),
+            (sigma, scalar, edge_resolution),
+            (sample_width, sample_height),
+            (blending, luma),
+        );
 
-                        monochrome.into_rgb()
-                    }
-                    ColourDepth::Rgb24 => {
-                        let true_color = Rgb24::from_rgb8(secondary);
+        let edges = &edges[((band_top - edges_top) * img_width) as usize..((band_bottom - edges_top) * img_width) as usize];
 
-                        true_color.into_rgb()
-                    }
-                    ColourDepth::Rgb16 => {
-                        let rgb16_color = Rgb565::from_rgb8(secondary);
+        let band = rows(band_top, band_bottom);
 
-                        rgb16_color.into_rgb()
-                    }
-                    ColourDepth::Ansi => {
-                        let ansi_color = Ansi::from_rgb8(secondary);
+        // The edges are only luma, so they're shown in grey (keeping the alpha of the original image so
+        // that transparency still works)
+        let edge_img: Option<Vec<u8>> = show_edges
+            .then(|| edges.iter().zip(band.chunks_exact(4)).flat_map(|(&edge, pixel)| [edge, edge, edge, pixel[3]]).collect());
 
-                        ansi_color.into_rgb()
-                    }
-                    ColourDepth::None => (255, 255, 255)
-                };
+        let source = edge_img.as_deref().unwrap_or(band);
+
+        // Everything that's needed from each sample apart from the pixels themselves: the sums of each
+        // channel, their squares and the alpha, and the total edge luma
+        let colour_sums = IntegralImage::from_fn(img_width, band_rows, |x, y| {
+            let idx = ((y * img_width + x) * 4) as usize;
+            let [r, g, b, a] = [source[idx], source[idx + 1], source[idx + 2], source[idx + 3]].map(u64::from);
+
+            [r, g, b, r * r, g * g, b * b, a]
+        });
+
+        let edge_sums = IntegralImage::from_fn(img_width, band_rows, |x, y| [blending.luma_weight(edges[(y * img_width + x) as usize])]);
+
+        let text: String = (0..band_rows.div_ceil(sample_height))
+            .into_par_iter()
+            .flat_map(|y| {
+                // Get references so that they aren't moved inside the closure
+                let colour_sums = &colour_sums;
 
-                let edge_sum: usize = edge_sample.pixels().count();
-                let edge_brightness: usize = edge_sample
-                    .pixels()
-                    .fold(0usize, |acc, (_, _, x)| acc + x.to_luma().0[0] as usize);
+                let edge_sums = &edge_sums;
 
-                let edge_avg = edge_brightness / edge_sum;
+                // Closure must be `move` because it may outlive `y`. Each of rayon's jobs gets its own
+                // buffer for the pixels, rather than every cell allocating its own.
+                (0..output_text_width).into_par_iter().map_init(Vec::new, move |pixels: &mut Vec<u8>, x| {
+                    // Samples along the right and bottom edges can be cut short by the edge of the image
+                    let (left, top) = (sample_width * x, sample_height * y);
+                    let width = sample_width.min(img_width - left);
+                    let height = sample_height.min(band_rows - top);
 
-                let edge_char_idx = (edge_avg as f64 / 255.0) * (EDGE_DETAIL.len() - 1) as f64;
+                    let pixel_count = width as u64 * height as u64;
 
-                let mut edge_char =
-                    EDGE_DETAIL[(edge_char_idx as usize).min(EDGE_DETAIL.len() - 1)] as char;
+                    pixels.clear();
 
-                if no_background {
-                    if edge_char == EDGE_DETAIL[0] as char {
-                        edge_char = EDGE_DETAIL[1] as char;
+                    for row in top..top + height {
+                        let start = ((row * img_width + left) * 4) as usize;
+
+                        pixels.extend_from_slice(&source[start..start + (width * 4) as usize]);
                     }
 
-                    format!(
-                        "{}{}",
-                        edge_char.truecolor(dominant_r, dominant_g, dominant_b),
-                        if x == output_text_width - 1 {
-                            '\n'
-                        } else {
-                            '\x00'
-                        }
-                    )
-                } else {
-                    format!(
-                        "{}{}",
-                        edge_char
-                            .truecolor(secondary_r, secondary_g, secondary_b)
-                            .on_truecolor(dominant_r, dominant_g, dominant_b),
-                        if x == output_text_width - 1 {
-                            '\n'
-                        } else {
-                            '\x00'
+                    let [r, g, b, r_squared, g_squared, b_squared, alpha_sum] = colour_sums.sum(left, top, width, height);
+
+                    let coverage = alpha_sum as f32 / (pixel_count * 255) as f32;
+
+                    // Flattening leaves fully opaque pixels alone, so the sums still hold after it
+                    let sums = (alpha_sum == pixel_count * 255).then_some([r, g, b, r_squared, g_squared, b_squared]);
+
+                    let end = if x == output_text_width - 1 {
+                        "\n"
+                    } else if depth == ColourDepth::None {
+                        ""
+                    } else {
+                        "\x00"
+                    };
+
+                    let colours = match alpha {
+                        AlphaMode::Empty if coverage < alpha_threshold => None,
+                        // Without a background colour, the glyph sits straight on top of the terminal's background,
+                        // so blending onto the matte would only muddy it. Just use whatever is actually visible.
+                        AlphaMode::Empty if no_background => dominant::pick(pixels, AlphaMode::Skip, matte, picker, blending, sums),
+                        _ => dominant::pick(pixels, alpha, matte, picker, blending, sums),
+                    };
+
+                    // Nothing visible in this sample, so leave the cell blank and let the terminal show through
+                    let Some([dominant, secondary]) = colours.map(|colours| colours.map(|x| x.into_rgb())) else {
+                        return format!("{}{}", ' ', end);
+                    };
+
+                    let edge_avg = blending.luma_from_sum(edge_sums.sum(left, top, width, height)[0], pixel_count);
+
+                    let (split, [dominant, secondary]) = match adaptive {
+                        None => (None, [dominant, secondary]),
+                        Some((sub_cell, detail_threshold)) => {
+                            let split = (edge_avg >= detail_threshold)
+                                .then(|| adaptive::split(pixels, (width, height), sub_cell, (alpha, matte), blending))
+                                .flatten();
+
+                            match split {
+                                Some((mask, colours)) => (Some((sub_cell, Some(mask))), colours.map(|x| x.into_rgb())),
+                                None => (Some((sub_cell, None)), [dominant, dominant]),
+                            }
                         }
-                    )
-                }
+                    };
+
+                    let distance = |colour: (u8, u8, u8)| background.map(|background| background.colour_distance(Rgb24::from(colour)));
+
+                    // Without a background colour, the glyph is all that shows, so it had better stand out
+                    // from the terminal's background
+                    let swap = no_background && distance(secondary) > distance(dominant);
+                    let [dominant, secondary] = if swap { [secondary, dominant] } else { [dominant, secondary] };
+
+                    let blends_in = |colour| distance(colour).is_some_and(|distance| distance < BACKGROUND_DISTANCE);
+
+                    if blends_in(dominant) && (no_background || blends_in(secondary)) {
+                        return format!("{}{}", ' ', end);
+                    }
+
+                    let adaptive_glyph = split.map(|(sub_cell, mask)| match mask {
+                        // Without a background, only the glyph gets drawn (in the first colour), so it has
+                        // to cover the parts in the first colour rather than the second.
+                        Some(mask) => sub_cell.glyph(if no_background != swap { !mask } else { mask }),
+                        None if no_background => sub_cell.full(),
+                        None => ' ',
+                    });
+
+                    let mut edge_char = adaptive_glyph.unwrap_or(ramp.glyph(edge_avg));
+
+                    if no_background && adaptive_glyph.is_none() && edge_char == ' ' {
+                        edge_char = ramp.faintest();
+                    }
+
+                    match (depth.quantise(dominant, (grey_levels, luma), theme), depth.quantise(secondary, (grey_levels, luma), theme)) {
+                        (Some(dominant), _) if no_background => format!("{}{}", edge_char.color(dominant), end),
+                        // The trait's `on_color` nests the foreground inside of the background, rather than
+                        // merging them into one escape code and resetting everything afterwards
+                        (Some(dominant), Some(secondary)) => format!("{}{}", OwoColorize::on_color(&edge_char.color(secondary), dominant), end),
+                        _ => format!("{}{}", edge_char, end),
+                    }
+                })
             })
-        })
-        .collect();
+            .collect();
+
+        output.write_all(text.as_bytes())?;
+    }
 
-    Ok(output)
+    Ok(())
 }
\ No newline at end of file
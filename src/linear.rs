@@ -0,0 +1,127 @@
+use std::sync::OnceLock;
+
+/// Image bytes are sRGB encoded, which means that adding them up and dividing (like the samplers and
+/// the blur do) gives colours that are darker than they should be. `Linear` decodes colours into
+/// linear light first, does the maths there, and encodes the result back into sRGB.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Blending {
+    /// Work on the sRGB bytes directly. This is what apixels has always done.
+    #[default]
+    Srgb,
+    /// Work in linear light.
+    Linear,
+}
+
+/// Converts an sRGB encoded channel into linear light, from 0 to 1.
+pub fn srgb_to_linear(channel: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+
+        for (idx, value) in table.iter_mut().enumerate() {
+            let c = idx as f32 / 255.0;
+
+            *value = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+        }
+
+        table
+    })[channel as usize]
+}
+
+/// Converts a linear light value (from 0 to 1, anything outside that is clamped) back into sRGB.
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+
+    let c = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (c * 255.0).round() as u8
+}
+
+impl Blending {
+    /// The plain mean of some pixels.
+    pub fn mean(self, pixels: &[[u8; 3]]) -> [u8; 3] {
+        let pixel_count = pixels.len();
+
+        match self {
+            Blending::Srgb => pixels
+                .iter()
+                .fold([0usize; 3], |[r_a, g_a, b_a], [r, g, b]| [r_a + *r as usize, g_a + *g as usize, b_a + *b as usize])
+                .map(|colour_sum| ((colour_sum + pixel_count / 2) / pixel_count) as u8),
+            Blending::Linear => pixels
+                .iter()
+                .fold([0f32; 3], |[r_a, g_a, b_a], [r, g, b]| {
+                    [r_a + srgb_to_linear(*r), g_a + srgb_to_linear(*g), b_a + srgb_to_linear(*b)]
+                })
+                .map(|colour_sum| linear_to_srgb(colour_sum / pixel_count as f32)),
+        }
+    }
+
+    /// The RMS mean of some pixels. Squaring the values is a rough way of undoing the sRGB gamma,
+    /// so in linear light this is just the mean.
+    pub fn rms(self, pixels: &[[u8; 3]]) -> [u8; 3] {
+        let pixel_count = pixels.len();
+
+        match self {
+            Blending::Srgb => pixels
+                .iter()
+                .fold(
+                    [0, 0, 0],
+                    |[r_a, g_a, b_a], [r, g, b]| {
+                                    [r_a + (*r as usize).pow(2), g_a + (*g as usize).pow(2), b_a + (*b as usize).pow(2)]
+                    })
+                .map(|colour_sum| ((colour_sum / pixel_count) as f64 + 1.0).sqrt() as u8 ),
+            Blending::Linear => self.mean(pixels),
+        }
+    }
+
+    /// Halfway between two colours.
+    pub fn mix(self, a: [u8; 3], b: [u8; 3]) -> [u8; 3] {
+        match self {
+            Blending::Srgb => [0, 1, 2].map(|idx| ((a[idx] as u16 + b[idx] as u16) / 2) as u8),
+            Blending::Linear => self.mean(&[a, b]),
+        }
+    }
+
+    /// The mean of some single channel (luma) values.
+    pub fn mean_luma(self, values: impl Iterator<Item = u8>) -> u8 {
+        match self {
+            Blending::Srgb => {
+                let (sum, count) = values.fold((0usize, 0usize), |(sum, count), value| (sum + value as usize, count + 1));
+
+                (sum / count) as u8
+            }
+            Blending::Linear => {
+                let (sum, count) = values.fold((0f32, 0usize), |(sum, count), value| (sum + srgb_to_linear(value), count + 1));
+
+                linear_to_srgb(sum / count as f32)
+            }
+        }
+    }
+
+    /// The value to put in an integral image for a luma value, so that [`Blending::luma_from_sum`]
+    /// can get the mean back out. Linear light values are stored as 16 bit fixed point.
+    pub fn luma_weight(self, luma: u8) -> u64 {
+        match self {
+            Blending::Srgb => luma as u64,
+            Blending::Linear => (srgb_to_linear(luma) * 65535.0).round() as u64,
+        }
+    }
+
+    /// The mean luma of `count` values, going by the sum of their [`Blending::luma_weight`]s.
+    /// Gives the same result as [`Blending::mean_luma`] (give or take rounding in linear light).
+    pub fn luma_from_sum(self, sum: u64, count: u64) -> u8 {
+        match self {
+            Blending::Srgb => (sum / count) as u8,
+            Blending::Linear => linear_to_srgb(sum as f32 / count as f32 / 65535.0),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use clap::ValueEnum;
+use crate::linear::{linear_to_srgb, srgb_to_linear};
+
+/// Ways of turning a colour into a single brightness value, for grayscale output and for working
+/// out how much edge there is in each cell.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Luma {
+    /// The plain mean of red, green and blue. Greens come out too dark and blues too bright
+    Average,
+    /// The SDTV weights: 0.299 red, 0.587 green and 0.114 blue
+    Rec601,
+    /// The HDTV (and sRGB) weights: 0.2126 red, 0.7152 green and 0.0722 blue
+    #[default]
+    Rec709,
+    /// The UHDTV weights: 0.2627 red, 0.678 green and 0.0593 blue
+    Rec2100,
+    /// The Rec. 709 weights applied in linear light, so the grey has exactly the same luminance as the colour
+    Luminance,
+    /// CIE L*, which is spaced out the way that eyes see brightness, scaled from 0-100 to 0-255
+    CieLightness,
+    /// The HSL lightness, halfway between the biggest and smallest channel
+    HslLightness,
+}
+
+impl Luma {
+    /// The brightness of a colour, from 0 to 255. Black is always 0 and white is always 255.
+    pub fn luma(self, (r, g, b): (u8, u8, u8)) -> u8 {
+        // The weights add up to 10,000
+        let weighted = |[r_weight, g_weight, b_weight]: [u32; 3]| {
+            ((r_weight * r as u32 + g_weight * g as u32 + b_weight * b as u32 + 5000) / 10000) as u8
+        };
+
+        let luminance = || 0.2126 * srgb_to_linear(r) + 0.7152 * srgb_to_linear(g) + 0.0722 * srgb_to_linear(b);
+
+        match self {
+            Luma::Average => ((r as u16 + g as u16 + b as u16) / 3) as u8,
+            Luma::Rec601 => weighted([2990, 5870, 1140]),
+            Luma::Rec709 => weighted([2126, 7152, 722]),
+            Luma::Rec2100 => weighted([2627, 6780, 593]),
+            Luma::Luminance => linear_to_srgb(luminance()),
+            Luma::CieLightness => {
+                // The cube root doesn't work near black, so L* is a straight line there instead
+                const EPSILON: f32 = 216.0 / 24389.0;
+                const KAPPA: f32 = 24389.0 / 27.0;
+
+                let y = luminance();
+                let lightness = if y > EPSILON { 116.0 * y.cbrt() - 16.0 } else { KAPPA * y };
+
+                (lightness / 100.0 * 255.0).round().clamp(0.0, 255.0) as u8
+            }
+            Luma::HslLightness => (r.max(g).max(b) as u16 + r.min(g).min(b) as u16).div_ceil(2) as u8,
+        }
+    }
+}
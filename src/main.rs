@@ -1,31 +1,279 @@
 #![feature(int_roundings)]
 #![feature(iter_array_chunks)]
-#![feature(array_chunks)]
 #![feature(unchecked_math)]
 
-mod colors;
-mod dominant;
-
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Read, Write};
 use std::path::Path;
-use apixels::into_ascii_controlled;
+use anyhow::bail;
+use apixels::{into_ascii_controlled, into_graphics};
 use apixels::Arguments;
+use apixels::batch::{self, Input};
+use apixels::chat;
+use apixels::colors::Rgb24;
+use apixels::cp437;
+use apixels::export::{self, Format};
+use apixels::grid::Grid;
+use apixels::linear::Blending;
+use apixels::preprocess::{Adjustment, Pipeline};
+use apixels::sauce;
+use apixels::sheet::{self, Tile};
+use apixels::theme::{BuiltinTheme, Theme};
+use apixels::transform::Transform;
+use image::ImageOutputFormat;
 use clap::Parser;
-
-const EDGE_DETAIL: [u8; 90] = *b" `-:_,^=;><+!rc*/z?sLTv)J7(|Fi{C}fI31tlu[neoZ5Yxjya]2ESwqkP6h9d4VpOGbUAKXHm8RD#$Bg0MNWQ%&@";
+use rayon::prelude::*;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Arguments::parse();
 
-    let img = std::fs::read(Path::new(&args.file_name))?;
+    let theme = args.theme()?;
+
+    // A theme knows what its background is, if the terminal wasn't asked
+    let background = args.background().or(theme.as_ref().and_then(|theme| theme.background));
+
+    if let Some(file) = args.output_file().filter(|file| args.format.is_none() && Format::from_extension(file).is_none()) {
+        bail!("There's no telling what format {} should be from its extension, so it needs `--format`", file.display());
+    }
+
+    let inputs = batch::expand(&args.file_name, args.recursive)?;
+
+    if let Some(columns) = args.sheet {
+        if args.output_dir.is_some() {
+            bail!("A contact sheet is all one file, so it goes to `--output` rather than `--output-dir`");
+        }
+
+        if args.graphics.is_some() {
+            bail!("Contact sheets are made of characters, so they can't use `--graphics`");
+        }
+
+        let mut output = open_output(&args)?;
+
+        return sheet(&args, &inputs, columns as usize, (theme.as_ref(), background), &mut output);
+    }
+
+    let Some(output_dir) = &args.output_dir else {
+        let [input] = inputs.as_slice() else {
+            bail!("There are {} files to draw, so they need `--output-dir` to go in (or `--sheet` to go side by side)", inputs.len());
+        };
+
+        let mut output = open_output(&args)?;
+
+        convert(&args, &input.path, (theme.as_ref(), background), &mut output)?;
+        output.flush()?;
+
+        return Ok(());
+    };
+
+    if args.output.is_some() {
+        bail!("`--output` is for one file, and `--output-dir` is for any number of them, so only one of them can be used");
+    }
+
+    if args.graphics.is_some() {
+        bail!("Graphics can only be shown in the terminal, not written to `--output-dir`");
+    }
+
+    // One file going wrong shouldn't stop the rest, so the errors are all saved up until the end
+    let paths = batch::output_paths(&inputs, &args.name_template, args.format().extension(), Path::new(output_dir))?;
+
+    let failures: Vec<(&Path, anyhow::Error)> = inputs
+        .par_iter()
+        .zip(&paths)
+        .filter_map(|(input, path)| {
+            let result = (|| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+
+                let mut output = BufWriter::new(File::create(path)?);
+                convert(&args, &input.path, (theme.as_ref(), background), &mut output)?;
+                output.flush()?;
+
+                anyhow::Ok(())
+            })();
+
+            // Half a file is no use to anyone
+            if result.is_err() {
+                std::fs::remove_file(path).ok();
+            }
+
+            result.err().map(|error| (input.path.as_path(), error))
+        })
+        .collect();
+
+    report(&failures, inputs.len())
+}
+
+/// Where the output goes: `--output`, or stdout.
+fn open_output(args: &Arguments) -> anyhow::Result<Box<dyn Write>> {
+    // Rows are written out as they're finished, rather than all at once at the end
+    Ok(match args.output_file() {
+        Some(file) => Box::new(BufWriter::new(File::create(file)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    })
+}
+
+/// Says what went wrong with each of the files that did, and fails if any of them did.
+fn report(failures: &[(&Path, anyhow::Error)], total: usize) -> anyhow::Result<()> {
+    for (path, error) in failures {
+        eprintln!("{}: {error}", path.display());
+    }
+
+    if !failures.is_empty() {
+        bail!("{} of {total} files couldn't be converted", failures.len());
+    }
+
+    Ok(())
+}
+
+/// Reads a file, or stdin if it's `-`.
+fn read(file_name: &Path) -> std::io::Result<Vec<u8>> {
+    if file_name == Path::new("-") {
+        let mut img = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut img)?;
+
+        Ok(img)
+    } else {
+        std::fs::read(file_name)
+    }
+}
 
+/// Draws one file into `output`, in whichever format was asked for.
+fn convert(args: &Arguments, file_name: &Path, (theme, background): (Option<&Theme>, Option<Rgb24>), output: &mut impl Write) -> anyhow::Result<()> {
+    let img = read(file_name)?;
+
+    if args.format() == Format::Ansi && !args.is_ansi(file_name, &img) {
+        render(args, img, &args.pipeline(), (theme, background), output)?;
+        writeln!(output)?;
+
+        return Ok(());
+    }
+
+    let (text, grid, palette) = read_grid(args, file_name, img, &args.pipeline(), (theme, background))?;
+
+    // The colours of characters that didn't say, which would have been the terminal's
+    let defaults = (palette.ansi[7], background.unwrap_or(Rgb24::from((0, 0, 0))));
+
+    write(args, &text, &grid, defaults, output)
+}
+
+/// Reads ANSI art (or draws an image, after running it through `pipeline`) into a grid of characters.
+/// Returns the text it came from, and the palette its 16 colours were looked up in, too.
+fn read_grid(args: &Arguments, file_name: &Path, img: Vec<u8>, pipeline: &Pipeline, (theme, background): (Option<&Theme>, Option<Rgb24>)) -> anyhow::Result<(String, Grid, Theme)> {
+    let text = if args.is_ansi(file_name, &img) {
+        img
+    } else {
+        let mut text = Vec::new();
+        render(args, img, pipeline, (theme, background), &mut text)?;
+        text
+    };
+
+    // `.ans` files were drawn for an 80 column terminal (unless SAUCE says otherwise), and count on
+    // it wrapping the lines
+    let wrap = file_name
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ans"))
+        .then(|| sauce::width(&text).map_or(80, usize::from));
+
+    // Text that isn't UTF-8 is most likely an old DOS `.ans` file
+    let text = String::from_utf8(text).unwrap_or_else(|error| cp437::decode(error.as_bytes()));
+
+    let palette = palette(args, theme, wrap.is_some());
+    let grid = Grid::parse(&text, &palette, wrap);
+
+    Ok((text, grid, palette))
+}
+
+/// The 16 colours to read ANSI art with, if there's no theme. DOS art was drawn in the VGA's colours,
+/// and that's what it's going back to.
+fn palette(args: &Arguments, theme: Option<&Theme>, dos: bool) -> Theme {
+    theme.cloned().unwrap_or_else(|| {
+        if dos || matches!(args.format(), Format::Ans | Format::Xbin) { BuiltinTheme::Vga.theme() } else { BuiltinTheme::Ubuntu.theme() }
+    })
+}
+
+/// Writes the grid out in whichever format was asked for. `text` is what the grid was read from.
+fn write(args: &Arguments, text: &str, grid: &Grid, defaults: (Rgb24, Rgb24), output: &mut impl Write) -> anyhow::Result<()> {
+    match args.format() {
+        Format::Ansi => output.write_all(text.as_bytes())?,
+        Format::Html => export::html(grid, defaults, output)?,
+        Format::Svg => export::svg(grid, defaults, args.export_cell_size(), output)?,
+        Format::Png => {
+            let mut png = Vec::new();
+            export::rasterise(grid, defaults, args.export_cell_size()).write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+            output.write_all(&png)?;
+        }
+        Format::Ans => export::ans(grid, defaults, args.ice, &args.sauce(), output)?,
+        Format::Xbin => export::xbin(grid, defaults, args.ice, &args.sauce(), output)?,
+        Format::Irc => chat::irc(grid, args.irc_colours, output)?,
+        Format::Discord => chat::discord(grid, output)?,
+        Format::Bbcode => chat::bbcode(grid, output)?,
+        Format::Cast => export::cast(text, grid, output)?,
+        Format::Json => export::json(grid, output)?,
+    }
+
+    Ok(())
+}
+
+/// Draws every file onto one contact sheet, `columns` to a row, and writes it out in whichever format
+/// was asked for. Files that can't be drawn leave an empty slot behind.
+fn sheet(args: &Arguments, inputs: &[Input], columns: usize, (theme, background): (Option<&Theme>, Option<Rgb24>), output: &mut impl Write) -> anyhow::Result<()> {
+    let slot = (args.tile_width as usize, args.tile_height as usize);
+
+    // Images are shrunk (or stretched) to fit in their slots before they're drawn, and ANSI art is cut off
+    let pipeline = args.pipeline().then(Adjustment::Transform(Transform::Fit(args.tile_width * args.sample_width, args.tile_height * args.sample_height)));
+
+    let (tiles, errors): (Vec<Tile>, Vec<Option<anyhow::Error>>) = inputs
+        .par_iter()
+        .map(|input| {
+            let caption = input.path.file_name().map_or_else(|| input.path.display().to_string(), |name| name.to_string_lossy().into_owned());
+
+            let grid = read(&input.path)
+                .map_err(anyhow::Error::from)
+                .and_then(|img| read_grid(args, &input.path, img, &pipeline, (theme, background)));
+
+            match grid {
+                Ok((_, grid, _)) => (Tile { grid, caption }, None),
+                Err(error) => (Tile { grid: Grid::default(), caption }, Some(error)),
+            }
+        })
+        .unzip();
+
+    let failures: Vec<(&Path, anyhow::Error)> = inputs
+        .iter()
+        .zip(errors)
+        .filter_map(|(input, error)| error.map(|error| (input.path.as_path(), error)))
+        .collect();
+
+    let grid = sheet::compose(&tiles, columns, slot, (args.gutter as usize, args.gutter_rows as usize), args.captions);
+
+    let mut text = Vec::new();
+    export::ansi(&grid, (args.depth(), args.grey_levels, args.luma), theme, &mut text)?;
+    let text = String::from_utf8(text)?;
+
+    let palette = palette(args, theme, false);
+    let defaults = (palette.ansi[7], background.unwrap_or(Rgb24::from((0, 0, 0))));
+
+    write(args, &text, &grid, defaults, output)?;
+    output.flush()?;
+
+    report(&failures, inputs.len())
+}
+
+/// Renders the image as characters (or graphics) into `output`, after running it through `pipeline`.
+fn render(args: &Arguments, img: Vec<u8>, pipeline: &Pipeline, (theme, background): (Option<&Theme>, Option<Rgb24>), output: &mut impl Write) -> anyhow::Result<()> {
     let sigma = args.sigma;
     let scalar = args.constant;
 
     let sample_width = args.sample_width;
     let sample_height = args.sample_height;
 
-    let output = into_ascii_controlled(img, (sigma, scalar), (sample_width, sample_height), args.no_background, args.edges, args.depth)?;
+    match args.graphics() {
+        Some(_) if args.format() != Format::Ansi => bail!("Graphics can only be shown in the terminal, with `--format ansi`"),
+        Some(graphics) => into_graphics(img, graphics, (sample_width, sample_height), args.cell_size(), (args.alpha, args.matte), args.sixel_colours, pipeline, args.memory_limit(), output)?,
+        None => into_ascii_controlled(img, (sigma, scalar, args.edge_resolution), (sample_width, sample_height), (args.no_background, background), args.edges, args.ramp(), (args.depth(), args.grey_levels, theme), (args.alpha, args.matte, args.alpha_threshold), args.picker.picker(), (if args.linear { Blending::Linear } else { Blending::Srgb }, args.luma), pipeline, args.adaptive.map(|sub_cell| (sub_cell, args.detail_threshold)), args.memory_limit(), output)?,
+    }
 
     // let edges: ImageBuffer<image::Rgb<u8>, _> = ImageBuffer::from_vec(
     //     img.width(),
@@ -187,7 +435,5 @@ async fn main() -> anyhow::Result<()> {
     //     })
     //     .collect();
 
-    println!("{output}");
-
     Ok(())
 }
@@ -0,0 +1,272 @@
+use std::cell::RefCell;
+use clap::ValueEnum;
+use color_thief::{Color as CtColor, ColorFormat};
+use crate::linear::Blending;
+
+/// Decides which two colours a sample gets turned into. The first colour is used for the
+/// background of the cell (or the glyph, with `--no-background`), the second one for the glyph.
+///
+/// Pixels have already been flattened (see [`crate::dominant::flatten`]) by the time they get
+/// here, and there is always at least one of them. Any averaging should go through `blending` (or
+/// [`Sample::mean`] and [`Sample::rms`], which might already know the answer).
+pub trait ColourPicker: Sync {
+    fn pick(&self, sample: &Sample, blending: Blending) -> [CtColor; 2];
+}
+
+/// The pixels of a sample, plus anything about them that has already been worked out elsewhere.
+#[derive(Copy, Clone, Debug)]
+pub struct Sample<'a> {
+    pub pixels: &'a [[u8; 3]],
+    /// The sums of the red, green and blue channels and then of their squares, if they're already
+    /// known (from an integral image, say). They must match `pixels`.
+    pub sums: Option<[u64; 6]>,
+}
+
+impl<'a> Sample<'a> {
+    pub fn new(pixels: &'a [[u8; 3]]) -> Self {
+        Self { pixels, sums: None }
+    }
+
+    /// Same as [`Blending::mean`], but skips going through the pixels when the sums are known.
+    pub fn mean(&self, blending: Blending) -> [u8; 3] {
+        let pixel_count = self.pixels.len() as u64;
+
+        match (blending, self.sums) {
+            (Blending::Srgb, Some([r, g, b, ..])) => [r, g, b].map(|colour_sum| ((colour_sum + pixel_count / 2) / pixel_count) as u8),
+            _ => blending.mean(self.pixels),
+        }
+    }
+
+    /// Same as [`Blending::rms`], but skips going through the pixels when the sums are known.
+    pub fn rms(&self, blending: Blending) -> [u8; 3] {
+        let pixel_count = self.pixels.len() as u64;
+
+        match (blending, self.sums) {
+            (Blending::Srgb, Some([_, _, _, r, g, b])) => [r, g, b].map(|colour_sum| ((colour_sum / pixel_count) as f64 + 1.0).sqrt() as u8),
+            _ => blending.rms(self.pixels),
+        }
+    }
+}
+
+/// The built in pickers, so that they can be chosen from the command line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Picker {
+    /// The most common colour averaged with the RMS mean, and the RMS mean
+    ModeMean,
+    /// The plain (arithmetic) mean for both colours. Gives flat blocks of colour
+    Mean,
+    /// The per-channel median for both colours
+    Median,
+    /// The centres of the two clusters found by k-means, bigger cluster first
+    KMeans,
+    /// The two colours in the sample that are furthest apart
+    Contrast,
+    /// The top two colours of the colour_thief (MMCQ) palette
+    Palette,
+}
+
+impl Picker {
+    pub fn picker(self) -> &'static dyn ColourPicker {
+        match self {
+            Picker::ModeMean => &ModeMean,
+            Picker::Mean => &Mean,
+            Picker::Median => &Median,
+            Picker::KMeans => &KMeans,
+            Picker::Contrast => &MaxContrast,
+            Picker::Palette => &Palette,
+        }
+    }
+}
+
+fn colour([r, g, b]: [u8; 3]) -> CtColor {
+    CtColor::new(r, g, b)
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(a, b)| (a.abs_diff(b) as u32).pow(2)).sum()
+}
+
+/// What apixels has always done: the most common colour averaged with the RMS mean, and the RMS mean.
+///
+/// "Most common" is worked out on a histogram with 4 bits per channel rather than on exact colours,
+/// and the colour that comes out of it is the mean of the pixels in the fullest bin. If two bins are
+/// equally full, the one that got there first wins.
+pub struct ModeMean;
+
+impl ModeMean {
+    const BITS: u32 = 4;
+
+    fn bin([r, g, b]: [u8; 3]) -> usize {
+        let shift = 8 - Self::BITS;
+
+        ((r as usize >> shift) << (2 * Self::BITS)) | ((g as usize >> shift) << Self::BITS) | (b as usize >> shift)
+    }
+}
+
+thread_local! {
+    // Reused between samples. Only the bins that get touched are cleared afterwards, which is a lot
+    // cheaper than zeroing the whole thing for every sample.
+    static HISTOGRAM: RefCell<Vec<u32>> = RefCell::new(vec![0; 1 << (3 * ModeMean::BITS)]);
+}
+
+impl ColourPicker for ModeMean {
+    fn pick(&self, sample: &Sample, blending: Blending) -> [CtColor; 2] {
+        let average = sample.rms(blending);
+
+        let fullest = HISTOGRAM.with(|histogram| {
+            let mut histogram = histogram.borrow_mut();
+
+            let (mut fullest, mut fullest_count) = (0, 0);
+
+            for pixel in sample.pixels {
+                let bin = Self::bin(*pixel);
+
+                histogram[bin] += 1;
+
+                if histogram[bin] > fullest_count {
+                    (fullest, fullest_count) = (bin, histogram[bin]);
+                }
+            }
+
+            for pixel in sample.pixels {
+                histogram[Self::bin(*pixel)] = 0;
+            }
+
+            fullest
+        });
+
+        let mode: Vec<[u8; 3]> = sample.pixels.iter().copied().filter(|pixel| Self::bin(*pixel) == fullest).collect();
+        let mode = blending.mean(&mode);
+
+        // The reason the dominant colour is averaged with the... average... is to reduce jagged edges in the image.
+        let dominant = blending.mix(mode, average);
+
+        [colour(dominant), colour(average)]
+    }
+}
+
+pub struct Mean;
+
+impl ColourPicker for Mean {
+    fn pick(&self, sample: &Sample, blending: Blending) -> [CtColor; 2] {
+        let mean = colour(sample.mean(blending));
+
+        [mean, mean]
+    }
+}
+
+/// The median is the same whether the colours are linear or not, so this ignores `blending`.
+pub struct Median;
+
+impl ColourPicker for Median {
+    fn pick(&self, sample: &Sample, _blending: Blending) -> [CtColor; 2] {
+        let median = [0, 1, 2].map(|channel| {
+            let mut values: Vec<u8> = sample.pixels.iter().map(|pixel| pixel[channel]).collect();
+
+            let middle = values.len() / 2;
+
+            *values.select_nth_unstable(middle).1
+        });
+
+        let median = colour(median);
+
+        [median, median]
+    }
+}
+
+/// k-means with k = 2. The clusters start off at the darkest and brightest pixels so that the
+/// result is always the same for the same sample.
+pub struct KMeans;
+
+impl KMeans {
+    const ITERATIONS: usize = 8;
+}
+
+impl ColourPicker for KMeans {
+    fn pick(&self, sample: &Sample, blending: Blending) -> [CtColor; 2] {
+        let pixels = sample.pixels;
+
+        let brightness = |[r, g, b]: &[u8; 3]| *r as u32 + *g as u32 + *b as u32;
+
+        let mut centres = [
+            *pixels.iter().min_by_key(|pixel| brightness(pixel)).unwrap(),
+            *pixels.iter().max_by_key(|pixel| brightness(pixel)).unwrap(),
+        ];
+
+        let mut clusters: [Vec<[u8; 3]>; 2] = [vec![], vec![]];
+
+        for _ in 0..Self::ITERATIONS {
+            clusters.iter_mut().for_each(Vec::clear);
+
+            for pixel in pixels {
+                let closest = (distance(*pixel, centres[1]) < distance(*pixel, centres[0])) as usize;
+
+                clusters[closest].push(*pixel);
+            }
+
+            let new_centres = [0, 1].map(|idx| if clusters[idx].is_empty() { centres[idx] } else { blending.mean(&clusters[idx]) });
+
+            if new_centres == centres {
+                break;
+            }
+
+            centres = new_centres;
+        }
+
+        if clusters[1].len() > clusters[0].len() {
+            centres.swap(0, 1);
+        }
+
+        centres.map(colour)
+    }
+}
+
+/// Finds (roughly) the two colours in the sample with the most contrast between them: the pixel
+/// furthest from the mean, and then the pixel furthest from that. Whichever of the two is closer to
+/// the mean ends up as the background.
+pub struct MaxContrast;
+
+impl ColourPicker for MaxContrast {
+    fn pick(&self, sample: &Sample, blending: Blending) -> [CtColor; 2] {
+        let pixels = sample.pixels;
+        let mean = sample.mean(blending);
+
+        let furthest_from = |target: [u8; 3]| *pixels.iter().max_by_key(|pixel| distance(**pixel, target)).unwrap();
+
+        let first = furthest_from(mean);
+        let second = furthest_from(first);
+
+        if distance(first, mean) <= distance(second, mean) {
+            [colour(first), colour(second)]
+        } else {
+            [colour(second), colour(first)]
+        }
+    }
+}
+
+/// Uses colour_thief's modified median cut quantisation. colour_thief ignores (nearly) white pixels
+/// and can't always make a palette out of a handful of pixels, so this falls back to [`Mean`] when
+/// it comes up short.
+///
+/// MMCQ only works to 5 bits a channel, so each colour of the palette is swapped for the pixel in the
+/// sample that's closest to it. Otherwise even a sample that's all one colour would come out as two
+/// slightly different ones.
+pub struct Palette;
+
+impl ColourPicker for Palette {
+    fn pick(&self, sample: &Sample, blending: Blending) -> [CtColor; 2] {
+        let bytes: Vec<u8> = sample.pixels.iter().flatten().copied().collect();
+
+        let closest = |&CtColor { r, g, b }: &CtColor| {
+            let target = [r, g, b];
+
+            colour(*sample.pixels.iter().min_by_key(|pixel| distance(**pixel, target)).unwrap())
+        };
+
+        match color_thief::get_palette(&bytes, ColorFormat::Rgb, 1, 2).as_deref() {
+            Ok([first, second, ..]) => [closest(first), closest(second)],
+            Ok([only]) => [closest(only); 2],
+            _ => Mean.pick(sample, blending),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use clap::ValueEnum;
+use image::{imageops, Rgba, RgbaImage};
+use crate::transform::Transform;
+
+/// A single tweak to the image before it gets turned into text. None of the colour adjustments touch
+/// the alpha channel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Adjustment {
+    /// Adds this to every channel (so -255 to 255)
+    Brightness(i32),
+    /// Percentage to increase (or, if negative, decrease) the contrast by
+    Contrast(f32),
+    /// Above 1 brightens the midtones, below 1 darkens them
+    Gamma(f32),
+    /// 0 is grayscale, 1 leaves the image alone, anything above that makes colours more vivid
+    Saturation(f32),
+    /// Rotates the hue by this many degrees
+    HueShift(i32),
+    /// An unsharp mask. Differences smaller than the threshold are left alone.
+    Sharpen { sigma: f32, threshold: i32 },
+    Invert,
+    /// Stretches each channel so that it covers the full range (ignoring the darkest and brightest
+    /// 0.5% of the image, so that a few stray pixels can't get in the way)
+    AutoLevels,
+    /// Histogram equalisation of the luma
+    Equalise,
+    /// Contrast limited adaptive histogram equalisation: equalises the luma of each tile in a
+    /// `tiles`×`tiles` grid separately, clipping each histogram to `clip_limit` times its average so
+    /// that flat areas don't get blown out.
+    Clahe { tiles: u32, clip_limit: f32 },
+    /// Cropping, rotating or flipping (these do change the size of the image)
+    Transform(Transform),
+}
+
+/// The different sorts of histogram work, for the command line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Levels {
+    /// Stretch each channel to cover the full range
+    Auto,
+    /// Equalise the histogram of the whole image
+    Equalise,
+    /// Equalise the histogram of each part of the image separately (CLAHE)
+    Clahe,
+}
+
+/// A list of adjustments, which are applied in the order they are added.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pipeline {
+    adjustments: Vec<Adjustment>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an adjustment to the end of the pipeline.
+    pub fn then(mut self, adjustment: Adjustment) -> Self {
+        self.adjustments.push(adjustment);
+
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.adjustments.is_empty()
+    }
+
+    pub fn apply(&self, mut img: RgbaImage) -> RgbaImage {
+        for adjustment in &self.adjustments {
+            adjustment.apply(&mut img);
+        }
+
+        img
+    }
+}
+
+impl Adjustment {
+    pub fn apply(&self, img: &mut RgbaImage) {
+        match *self {
+            Adjustment::Brightness(value) => imageops::colorops::brighten_in_place(img, value),
+            Adjustment::Contrast(contrast) => imageops::colorops::contrast_in_place(img, contrast),
+            Adjustment::Gamma(gamma) => {
+                let table: [u8; 256] = std::array::from_fn(|idx| ((idx as f32 / 255.0).powf(1.0 / gamma) * 255.0).round() as u8);
+
+                map_channels(img, |_, value| table[value as usize]);
+            }
+            Adjustment::Saturation(saturation) => {
+                for Rgba([r, g, b, _]) in img.pixels_mut() {
+                    let luma = luma(*r, *g, *b) as f32;
+
+                    for channel in [r, g, b] {
+                        *channel = (luma + (*channel as f32 - luma) * saturation).round().clamp(0.0, 255.0) as u8;
+                    }
+                }
+            }
+            Adjustment::HueShift(degrees) => imageops::colorops::huerotate_in_place(img, degrees),
+            Adjustment::Sharpen { sigma, threshold } => *img = imageops::unsharpen(img, sigma, threshold),
+            Adjustment::Invert => imageops::colorops::invert(img),
+            Adjustment::AutoLevels => auto_levels(img),
+            Adjustment::Equalise => equalise(img),
+            Adjustment::Clahe { tiles, clip_limit } => clahe(img, tiles, clip_limit),
+            Adjustment::Transform(transform) => *img = transform.apply(img),
+        }
+    }
+}
+
+/// Rec. 601 luma, which is what the equalisers work on.
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114 + 500) / 1000) as u8
+}
+
+fn map_channels(img: &mut RgbaImage, mut map: impl FnMut(usize, u8) -> u8) {
+    for Rgba([r, g, b, _]) in img.pixels_mut() {
+        for (idx, channel) in [r, g, b].into_iter().enumerate() {
+            *channel = map(idx, *channel);
+        }
+    }
+}
+
+fn auto_levels(img: &mut RgbaImage) {
+    const CLIP: f32 = 0.005;
+
+    let mut histograms = [[0usize; 256]; 3];
+
+    for Rgba([r, g, b, _]) in img.pixels() {
+        histograms[0][*r as usize] += 1;
+        histograms[1][*g as usize] += 1;
+        histograms[2][*b as usize] += 1;
+    }
+
+    let clipped = ((img.width() * img.height()) as f32 * CLIP) as usize;
+
+    // The first value (going through `values` in order) that more than `clipped` pixels are at or past
+    fn first_past_clip(histogram: &[usize; 256], values: impl Iterator<Item = usize>, clipped: usize) -> usize {
+        let mut seen = 0;
+
+        for value in values {
+            seen += histogram[value];
+
+            if seen > clipped {
+                return value;
+            }
+        }
+
+        0
+    }
+
+    let tables = histograms.map(|histogram| {
+        let low = first_past_clip(&histogram, 0..256, clipped);
+        let high = first_past_clip(&histogram, (0..256).rev(), clipped);
+
+        let table: [u8; 256] = std::array::from_fn(|value| {
+            if high <= low {
+                value as u8
+            } else {
+                ((value.clamp(low, high) - low) as f32 * 255.0 / (high - low) as f32).round() as u8
+            }
+        });
+
+        table
+    });
+
+    map_channels(img, |idx, value| tables[idx][value as usize]);
+}
+
+/// Turns a histogram into a lookup table that spreads the values out evenly. The counts don't have to
+/// be whole numbers, since CLAHE shares the clipped counts out between all of them.
+fn equalisation_table(histogram: &[f64; 256]) -> [u8; 256] {
+    let total: f64 = histogram.iter().sum();
+
+    let mut cumulative = 0.0;
+
+    let cdf = histogram.map(|count| {
+        cumulative += count;
+
+        cumulative
+    });
+
+    let cdf_min = cdf.iter().copied().find(|count| *count > 0.0).unwrap_or_default();
+
+    if total <= cdf_min {
+        // Only one value in the whole histogram (or none at all), so there's nothing to spread out
+        return std::array::from_fn(|value| value as u8);
+    }
+
+    cdf.map(|count| ((count - cdf_min).max(0.0) * 255.0 / (total - cdf_min)).round() as u8)
+}
+
+/// Changes the luma of a pixel to `new_luma` (from `old_luma`) while keeping its colour the same,
+/// by shifting it along the luma axis of YCbCr.
+fn relight(Rgba([r, g, b, _]): &mut Rgba<u8>, old_luma: u8, new_luma: u8) {
+    let shift = new_luma as i16 - old_luma as i16;
+
+    for channel in [r, g, b] {
+        *channel = (*channel as i16 + shift).clamp(0, 255) as u8;
+    }
+}
+
+fn equalise(img: &mut RgbaImage) {
+    let mut histogram = [0.0; 256];
+
+    for Rgba([r, g, b, _]) in img.pixels() {
+        histogram[luma(*r, *g, *b) as usize] += 1.0;
+    }
+
+    let table = equalisation_table(&histogram);
+
+    for pixel in img.pixels_mut() {
+        let Rgba([r, g, b, _]) = *pixel;
+        let old_luma = luma(r, g, b);
+
+        relight(pixel, old_luma, table[old_luma as usize]);
+    }
+}
+
+fn clahe(img: &mut RgbaImage, tiles: u32, clip_limit: f32) {
+    let (width, height) = img.dimensions();
+
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let tile_width = width.div_ceil(tiles.clamp(1, width));
+    let tile_height = height.div_ceil(tiles.clamp(1, height));
+
+    // Rounding the tiles' size up can leave fewer of them than asked for (20 pixels in 8 tiles is 7
+    // tiles of 3), and there mustn't be any empty ones on the end
+    let tiles_x = width.div_ceil(tile_width);
+    let tiles_y = height.div_ceil(tile_height);
+
+    let mut histograms = vec![[0.0; 256]; (tiles_x * tiles_y) as usize];
+
+    for (x, y, Rgba([r, g, b, _])) in img.enumerate_pixels() {
+        let tile = (y / tile_height) * tiles_x + x / tile_width;
+
+        histograms[tile as usize][luma(*r, *g, *b) as usize] += 1.0;
+    }
+
+    let tables: Vec<[u8; 256]> = histograms
+        .into_iter()
+        .map(|mut histogram| {
+            let total: f64 = histogram.iter().sum();
+            let limit = total / 256.0 * clip_limit as f64;
+
+            // Everything above the limit gets spread out evenly over the whole histogram
+            let excess: f64 = histogram.iter().map(|count| (count - limit).max(0.0)).sum();
+
+            for count in histogram.iter_mut() {
+                *count = count.min(limit) + excess / 256.0;
+            }
+
+            equalisation_table(&histogram)
+        })
+        .collect();
+
+    // Each pixel gets a mix of the tables of the four tiles whose centres surround it, so that there
+    // aren't any visible seams between tiles.
+    let position = |coord: u32, tile_size: u32, tile_count: u32| {
+        let centre = (coord as f32 + 0.5) / tile_size as f32 - 0.5;
+        let low = centre.floor().clamp(0.0, (tile_count - 1) as f32);
+        let high = (low + 1.0).min((tile_count - 1) as f32);
+        let weight = (centre - low).clamp(0.0, 1.0);
+
+        (low as u32, high as u32, weight)
+    };
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let Rgba([r, g, b, _]) = *pixel;
+        let old_luma = luma(r, g, b);
+
+        let (left, right, x_weight) = position(x, tile_width, tiles_x);
+        let (top, bottom, y_weight) = position(y, tile_height, tiles_y);
+
+        let lookup = |tile_x: u32, tile_y: u32| tables[(tile_y * tiles_x + tile_x) as usize][old_luma as usize] as f32;
+
+        let top_row = lookup(left, top) * (1.0 - x_weight) + lookup(right, top) * x_weight;
+        let bottom_row = lookup(left, bottom) * (1.0 - x_weight) + lookup(right, bottom) * x_weight;
+
+        let new_luma = (top_row * (1.0 - y_weight) + bottom_row * y_weight).round() as u8;
+
+        relight(pixel, old_luma, new_luma);
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::Write;
+use std::time::SystemTime;
+use crate::cp437;
+
+/// What SAUCE says the file is. Only the two kinds apixels writes are here.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// An `.ans` file `width` × `height` characters big, which may use iCE colours
+    Ansi { width: u16, height: u16, ice: bool },
+    /// An `.xb` file `width` × `height` characters big. Its font and palette are in its own header
+    Xbin { width: u16, height: u16 },
+}
+
+/// The SAUCE record on the end of a piece of ANSI art, which is where art viewers (and archives like
+/// 16colo.rs) look for who made it, and how it should be shown.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sauce {
+    pub title: String,
+    pub author: String,
+    pub group: String,
+    /// `CCYYMMDD`
+    pub date: String,
+    /// The font it was drawn for, like `IBM VGA`
+    pub font: String,
+}
+
+/// The date (in UTC) as SAUCE writes it, `CCYYMMDD`.
+pub fn date(time: SystemTime) -> String {
+    let days = time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |duration| duration.as_secs() / 86400) as i64;
+
+    // Howard Hinnant's `civil_from_days`, with the year starting in March so leap days come last
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{year:04}{month:02}{day:02}")
+}
+
+/// Writes `text` into a field `length` bytes long, in CP437, cut short or padded out with `padding`.
+fn field(text: &str, length: usize, padding: u8, output: &mut Vec<u8>) {
+    let mut bytes: Vec<u8> = text.chars().map(|character| cp437::encode(character).unwrap_or(b'?')).collect();
+
+    bytes.resize(length, padding);
+    output.extend(bytes);
+}
+
+impl Sauce {
+    /// Writes the record, after the `^Z` that marks the end of the art. `file_size` is how many bytes
+    /// of art came before it.
+    pub fn write(&self, kind: Kind, file_size: usize, output: &mut impl Write) -> std::io::Result<()> {
+        // Data type and file type, then the four numbers and the flags, whose meanings depend on them
+        let ((data_type, file_type), (width, height), flags, font) = match kind {
+            // The flags' first bit turns blinking into bright backgrounds
+            Kind::Ansi { width, height, ice } => ((1, 1), (width, height), ice as u8, self.font.as_str()),
+            // TInfoS is only for character kinds, XBin's font (if it has one) is in the file
+            Kind::Xbin { width, height } => ((6, 0), (width, height), 0, ""),
+        };
+
+        let mut record = Vec::with_capacity(129);
+
+        record.push(0x1a);
+        record.extend(b"SAUCE00");
+        field(&self.title, 35, b' ', &mut record);
+        field(&self.author, 20, b' ', &mut record);
+        field(&self.group, 20, b' ', &mut record);
+        field(&self.date, 8, b' ', &mut record);
+        record.extend((file_size as u32).to_le_bytes());
+        record.extend([data_type, file_type]);
+        record.extend(width.to_le_bytes());
+        record.extend(height.to_le_bytes());
+        record.extend([0; 4]);
+        // No comments
+        record.push(0);
+        record.push(flags);
+        field(font, 22, 0, &mut record);
+
+        output.write_all(&record)
+    }
+}
+
+/// The width the SAUCE record on the end of an `.ans` file gives, if it has one that says.
+pub fn width(bytes: &[u8]) -> Option<u16> {
+    let record = bytes.get(bytes.len().checked_sub(128)?..)?;
+
+    // Character data, as ANSI (or plain ASCII, or ANSI animation)
+    if !record.starts_with(b"SAUCE") || record[94] != 1 || !(0..=2).contains(&record[95]) {
+        return None;
+    }
+
+    Some(u16::from_le_bytes([record[96], record[97]])).filter(|width| *width > 0)
+}
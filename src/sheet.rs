@@ -0,0 +1,62 @@
+use crate::grid::{Cell, Grid};
+
+/// One drawing on a contact sheet, and the caption that goes under it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub grid: Grid,
+    pub caption: String,
+}
+
+/// The caption, cut down to `width` characters (with an ellipsis, so it's obvious something's
+/// missing) and centred.
+fn caption(text: &str, width: usize) -> Vec<char> {
+    let mut characters: Vec<char> = text.chars().collect();
+
+    if characters.len() > width {
+        characters.truncate(width.saturating_sub(1));
+
+        if width > 0 {
+            characters.push('…');
+        }
+    }
+
+    let padding = (width - characters.len()) / 2;
+
+    std::iter::repeat_n(' ', padding).chain(characters).collect()
+}
+
+/// Lays the tiles out `columns` to a row, each one centred in a slot `slot` characters big, with
+/// `gutter` columns and rows of space between them. With `captions`, every slot gets another row
+/// underneath for its tile's caption.
+pub fn compose(tiles: &[Tile], columns: usize, (slot_width, slot_height): (usize, usize), (gutter_columns, gutter_rows): (usize, usize), captions: bool) -> Grid {
+    let columns = columns.clamp(1, tiles.len().max(1));
+    let rows = tiles.len().div_ceil(columns);
+
+    let cell_height = slot_height + captions as usize;
+
+    let mut sheet = Grid::blank(
+        (columns * (slot_width + gutter_columns)).saturating_sub(gutter_columns),
+        (rows * (cell_height + gutter_rows)).saturating_sub(gutter_rows),
+    );
+
+    for (index, tile) in tiles.iter().enumerate() {
+        let (left, top) = ((index % columns) * (slot_width + gutter_columns), (index / columns) * (cell_height + gutter_rows));
+
+        // Tiles only ever come out smaller than their slots in one direction, but they're centred in both anyway
+        let (x, y) = (slot_width.saturating_sub(tile.grid.width) / 2, slot_height.saturating_sub(tile.grid.height) / 2);
+
+        let mut slot = Grid::blank(slot_width, slot_height);
+        slot.paste(&tile.grid, (x, y));
+        sheet.paste(&slot, (left, top));
+
+        if captions {
+            let start = (top + slot_height) * sheet.width + left;
+
+            for (offset, character) in caption(&tile.caption, slot_width).into_iter().enumerate() {
+                sheet.cells[start + offset] = Cell { character, ..Cell::default() };
+            }
+        }
+    }
+
+    sheet
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use color_thief::ColorFormat;
+use image::RgbaImage;
+use rayon::prelude::*;
+use crate::colors::Rgb24;
+use crate::dominant::{self, AlphaMode};
+
+/// Pixels less opaque than this are left undrawn (unless everything is being composited), so that
+/// the terminal shows through them.
+const OPAQUE_FROM: u8 = 128;
+
+/// Picks a palette of at most `colours` colours for the pixels. If there aren't any more colours than
+/// that in the first place, they're used as they are, and otherwise colour_thief's median cut picks them.
+pub fn palette(pixels: &[[u8; 3]], colours: u16) -> Vec<[u8; 3]> {
+    let mut distinct = BTreeSet::new();
+
+    for pixel in pixels {
+        distinct.insert(*pixel);
+
+        if distinct.len() > colours as usize {
+            break;
+        }
+    }
+
+    if distinct.len() <= colours as usize {
+        return distinct.into_iter().collect();
+    }
+
+    let bytes: Vec<u8> = pixels.iter().flatten().copied().collect();
+
+    // colour_thief leaves (nearly) white pixels out, so white gets a slot of its own if there are any
+    let white = pixels.iter().any(|pixel| pixel.iter().all(|channel| *channel > 250));
+    let wanted = (colours - white as u16).min(255) as usize;
+
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+
+    // It also pads the palette out with copies when it runs out of boxes to split
+    for colour in color_thief::get_palette(&bytes, ColorFormat::Rgb, 1, wanted.max(2) as u8).unwrap_or_default() {
+        if palette.len() < wanted && !palette.contains(&[colour.r, colour.g, colour.b]) {
+            palette.push([colour.r, colour.g, colour.b]);
+        }
+    }
+
+    if white || palette.is_empty() {
+        palette.push([255, 255, 255]);
+    }
+
+    palette
+}
+
+/// The index of the colour in `palette` that's closest to `colour`.
+pub fn nearest(palette: &[[u8; 3]], colour: [u8; 3]) -> u8 {
+    let distance = |entry: &[u8; 3]| -> i32 {
+        entry.iter().zip(colour).map(|(a, b)| (*a as i32 - b as i32).pow(2)).sum()
+    };
+
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map_or(0, |(index, _)| index as u8)
+}
+
+/// Writes the image out as a sixel stream, with a palette of up to `colours` colours (at most 256).
+/// Partly transparent pixels are blended onto `matte`, and with any alpha mode other than
+/// `Composite`, mostly transparent ones aren't drawn at all.
+pub fn encode(img: &RgbaImage, colours: u16, (alpha, matte): (AlphaMode, Rgb24), output: &mut impl Write) -> std::io::Result<()> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+
+    let pixels = dominant::flatten(img.as_raw(), AlphaMode::Composite, matte);
+    let drawn: Vec<bool> = img.pixels().map(|pixel| alpha == AlphaMode::Composite || pixel[3] >= OPAQUE_FROM).collect();
+
+    let visible: Vec<[u8; 3]> = pixels.iter().zip(&drawn).filter(|(_, drawn)| **drawn).map(|(pixel, _)| *pixel).collect();
+    let palette = palette(&visible, colours);
+
+    let indices: Vec<Option<u8>> = pixels
+        .par_iter()
+        .zip(drawn.par_iter())
+        .map(|(pixel, drawn)| drawn.then(|| nearest(&palette, *pixel)))
+        .collect();
+
+    // The second parameter being 1 leaves pixels that aren't drawn alone, instead of filling them in
+    // with colour 0. The raster attributes give the pixel aspect ratio (1:1) and the size.
+    write!(output, "\x1bP0;1;0q\"1;1;{width};{height}")?;
+
+    // Sixel colours are given as percentages
+    let percent = |channel: u8| (channel as u32 * 100 + 127) / 255;
+
+    for (index, [r, g, b]) in palette.iter().enumerate() {
+        write!(output, "#{index};2;{};{};{}", percent(*r), percent(*g), percent(*b))?;
+    }
+
+    // Each sixel is a column of 6 pixels, so the image goes out in bands of 6 rows. Every colour that
+    // shows up in a band gets a pass over it, with `$` going back to the start of the band in between.
+    for band_top in (0..height).step_by(6) {
+        let band_rows = (height - band_top).min(6);
+        let band = &indices[band_top * width..(band_top + band_rows) * width];
+
+        let mut used = [false; 256];
+        band.iter().flatten().for_each(|index| used[*index as usize] = true);
+
+        let mut text = Vec::new();
+
+        for colour in (0..palette.len()).filter(|colour| used[*colour]) {
+            let sixels = (0..width).map(|x| {
+                (0..band_rows)
+                    .filter(|row| band[row * width + x] == Some(colour as u8))
+                    .fold(0u8, |bits, row| bits | 1 << row)
+            });
+
+            if !text.is_empty() {
+                text.push(b'$');
+            }
+
+            write!(text, "#{colour}")?;
+            run_length_encode(sixels, &mut text)?;
+        }
+
+        if band_top + 6 < height {
+            text.push(b'-');
+        }
+
+        output.write_all(&text)?;
+    }
+
+    write!(output, "\x1b\\")
+}
+
+/// Writes out the sixels (each one being 6 bits, one for each row), with runs of more than 3 of the
+/// same one written as `!` and a count. Empty sixels at the end of the row are left off.
+fn run_length_encode(sixels: impl Iterator<Item = u8>, output: &mut Vec<u8>) -> std::io::Result<()> {
+    let mut runs: Vec<(u8, usize)> = Vec::new();
+
+    for sixel in sixels {
+        match runs.last_mut() {
+            Some((last, count)) if *last == sixel => *count += 1,
+            _ => runs.push((sixel, 1)),
+        }
+    }
+
+    if runs.last().is_some_and(|(sixel, _)| *sixel == 0) {
+        runs.pop();
+    }
+
+    for (sixel, count) in runs {
+        let character = b'?' + sixel;
+
+        if count > 3 {
+            write!(output, "!{count}{}", character as char)?;
+        } else {
+            output.extend(std::iter::repeat_n(character, count));
+        }
+    }
+
+    Ok(())
+}
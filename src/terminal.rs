@@ -0,0 +1,328 @@
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::time::Duration;
+use anyhow::bail;
+use crate::colors::Rgb24;
+use crate::{ColourDepth, Graphics};
+
+/// What a terminal's terminfo entry says about its colours.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Terminfo {
+    /// The `colors` capability
+    pub colours: Option<i32>,
+    /// Whether it has the `Tc` or `RGB` extension, which means it takes 24 bit colour
+    pub truecolour: bool,
+}
+
+impl Terminfo {
+    /// Looks `term` up in the terminfo database, if there is one.
+    pub fn lookup(term: &str) -> Option<Terminfo> {
+        let database = terminfo::Database::from_name(term).ok()?;
+
+        Some(Terminfo {
+            colours: database.get::<terminfo::capability::MaxColors>().map(Into::into),
+            truecolour: database.raw("Tc").is_some() || database.raw("RGB").is_some(),
+        })
+    }
+}
+
+/// Terminals that take 24 bit colour, but don't always set `COLORTERM` to say so.
+const TRUECOLOUR_PROGRAMS: [&str; 5] = ["iTerm.app", "WezTerm", "vscode", "ghostty", "Hyper"];
+const TRUECOLOUR_TERMS: [&str; 5] = ["xterm-kitty", "alacritty", "foot", "wezterm", "xterm-ghostty"];
+
+/// The best colour depth for wherever the output is going: stdout, or a file if `to_file`.
+pub fn detect(to_file: bool) -> ColourDepth {
+    detect_depth(|name| std::env::var(name).ok(), !to_file && std::io::stdout().is_terminal(), Terminfo::lookup)
+}
+
+/// Works out the best colour depth from the environment variables (`var`), whether the output is
+/// going to a terminal and what the terminfo database says.
+///
+/// Output that isn't going to a terminal is plain text, and so is everything when `NO_COLOR` is set.
+/// `FORCE_COLOR` overrides both of those: `1` (or anything else) means at least 16 colours, `2` at
+/// least 256 and `3` 24 bit, and `0` or `false` means no colour at all.
+pub fn detect_depth(
+    var: impl Fn(&str) -> Option<String>,
+    is_terminal: bool,
+    terminfo: impl Fn(&str) -> Option<Terminfo>,
+) -> ColourDepth {
+    // Empty variables count as not being set, going by https://no-color.org and https://force-color.org
+    let var = |name: &str| var(name).filter(|value| !value.is_empty());
+
+    let forced = match var("FORCE_COLOR").as_deref() {
+        None => None,
+        Some("0" | "false") => return ColourDepth::None,
+        Some("2") => Some(ColourDepth::Ansi256),
+        Some("3") => Some(ColourDepth::Rgb24),
+        Some(_) => Some(ColourDepth::Ansi),
+    };
+
+    if forced.is_none() && (var("NO_COLOR").is_some() || !is_terminal) {
+        return ColourDepth::None;
+    }
+
+    let detected = detect_terminal(&var, &terminfo);
+
+    let rank = |depth: ColourDepth| match depth {
+        ColourDepth::Rgb24 => 3,
+        ColourDepth::Ansi256 => 2,
+        ColourDepth::Ansi => 1,
+        _ => 0,
+    };
+
+    match forced {
+        Some(minimum) if rank(minimum) > rank(detected) => minimum,
+        _ => detected,
+    }
+}
+
+fn detect_terminal(var: &impl Fn(&str) -> Option<String>, terminfo: &impl Fn(&str) -> Option<Terminfo>) -> ColourDepth {
+    let term = var("TERM").unwrap_or_default();
+
+    if matches!(var("COLORTERM").as_deref(), Some("truecolor" | "24bit")) {
+        return ColourDepth::Rgb24;
+    }
+
+    if term == "dumb" {
+        return ColourDepth::None;
+    }
+
+    let truecolour_program = var("TERM_PROGRAM").is_some_and(|program| TRUECOLOUR_PROGRAMS.contains(&program.as_str()));
+
+    // Windows Terminal sets WT_SESSION instead of TERM
+    if truecolour_program || var("WT_SESSION").is_some() || TRUECOLOUR_TERMS.contains(&term.as_str()) || term.ends_with("-direct") {
+        return ColourDepth::Rgb24;
+    }
+
+    if let Some(info) = (!term.is_empty()).then(|| terminfo(&term)).flatten() {
+        return match info.colours.unwrap_or(0) {
+            _ if info.truecolour => ColourDepth::Rgb24,
+            colours if colours >= 1 << 24 => ColourDepth::Rgb24,
+            colours if colours >= 256 => ColourDepth::Ansi256,
+            colours if colours >= 8 => ColourDepth::Ansi,
+            _ => ColourDepth::None,
+        };
+    }
+
+    if term.ends_with("256color") {
+        ColourDepth::Ansi256
+    } else if !term.is_empty() || cfg!(windows) {
+        // Some sort of terminal, even if it's not one that anything knows about
+        ColourDepth::Ansi
+    } else {
+        ColourDepth::None
+    }
+}
+
+/// How long to wait for the terminal to answer a question about itself, in case it never does.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The colour of the terminal's background, so that the output can be made to stand out from it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    /// Ask the terminal
+    Auto,
+    /// Taken to be white
+    Light,
+    /// Taken to be black
+    Dark,
+    Colour(Rgb24),
+}
+
+impl Background {
+    /// The actual colour of the background, or `None` if the terminal didn't say.
+    pub fn resolve(self) -> Option<Rgb24> {
+        match self {
+            Background::Auto => query_background(QUERY_TIMEOUT),
+            Background::Light => Some(Rgb24::from((255, 255, 255))),
+            Background::Dark => Some(Rgb24::from((0, 0, 0))),
+            Background::Colour(colour) => Some(colour),
+        }
+    }
+}
+
+impl FromStr for Background {
+    type Err = anyhow::Error;
+
+    /// Parses `auto`, `light`, `dark` or `#rrggbb`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Background::Auto),
+            "light" => Ok(Background::Light),
+            "dark" => Ok(Background::Dark),
+            _ if s.starts_with('#') => Ok(Background::Colour(s.parse()?)),
+            _ => bail!("Expected auto, light, dark or #rrggbb, got `{s}`"),
+        }
+    }
+}
+
+/// Parses the terminal's answer to an OSC 11 query, which looks like `ESC ] 11 ; rgb:RRRR/GGGG/BBBB`
+/// followed by `ESC \` or `BEL`. Each channel can have anything from 1 to 4 hex digits.
+pub fn parse_background_reply(reply: &[u8]) -> Option<Rgb24> {
+    let reply = std::str::from_utf8(reply).ok()?;
+    let start = reply.find("rgb:")? + 4;
+
+    let channels: Vec<u8> = reply[start..]
+        .split(['/', '\x1b', '\x07'])
+        .take(3)
+        .map(|channel| {
+            if channel.is_empty() || channel.len() > 4 {
+                return None;
+            }
+
+            let value = u32::from_str_radix(channel, 16).ok()?;
+            let max = (1u32 << (4 * channel.len())) - 1;
+
+            Some(((value * 255 + max / 2) / max) as u8)
+        })
+        .collect::<Option<_>>()?;
+
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+
+    Some(Rgb24::from((r, g, b)))
+}
+
+/// Asks the terminal for its background colour with OSC 11, waiting at most `timeout` for an answer.
+pub fn query_background(timeout: Duration) -> Option<Rgb24> {
+    parse_background_reply(&query(b"\x1b]11;?\x1b\\", timeout))
+}
+
+/// Asks the terminal how big each character is in pixels, as (width, height).
+pub fn query_cell_size(timeout: Duration) -> Option<(u32, u32)> {
+    parse_cell_size_reply(&query(b"\x1b[16t", timeout))
+}
+
+/// How big each character is in pixels, if the terminal says.
+pub fn detect_cell_size() -> Option<(u32, u32)> {
+    query_cell_size(QUERY_TIMEOUT)
+}
+
+/// Parses the answer to `CSI 16 t`, which is `ESC [ 6 ; height ; width t`.
+pub fn parse_cell_size_reply(reply: &[u8]) -> Option<(u32, u32)> {
+    let reply = std::str::from_utf8(reply).ok()?;
+    let start = reply.find("\x1b[6;")? + 4;
+    let end = start + reply[start..].find('t')?;
+
+    let (height, width) = reply[start..end].split_once(';')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+/// Parses the answer to the primary device attributes request (`CSI c`), which is `ESC [ ? 62 ; 4 ; 22 c`
+/// or similar, into the list of numbers in it. The first is the terminal's conformance level and the
+/// rest are the extra things it can do (4 being sixel graphics).
+pub fn parse_attributes_reply(reply: &[u8]) -> Option<Vec<u32>> {
+    let reply = std::str::from_utf8(reply).ok()?;
+    let start = reply.rfind("\x1b[?")? + 3;
+    let end = start + reply[start..].find('c')?;
+
+    reply[start..end].split(';').map(|attribute| attribute.parse().ok()).collect()
+}
+
+/// Whether the terminal says it can show sixel graphics.
+pub fn query_sixel(timeout: Duration) -> bool {
+    parse_attributes_reply(&query(b"", timeout)).is_some_and(|attributes| attributes[1..].contains(&4))
+}
+
+/// The best kind of graphics the terminal can show, if any.
+pub fn detect_graphics() -> Option<Graphics> {
+    detect_graphics_with(|name| std::env::var(name).ok(), std::io::stdout().is_terminal(), || query_sixel(QUERY_TIMEOUT))
+}
+
+/// Works out the best kind of graphics from the environment variables (`var`) and whether the output
+/// is going to a terminal. Kitty's and iTerm2's protocols can only be recognised by the terminal's
+/// name, but terminals say for themselves whether they can do sixels, so `sixel` is only asked if
+/// it's neither of those.
+pub fn detect_graphics_with(
+    var: impl Fn(&str) -> Option<String>,
+    is_terminal: bool,
+    sixel: impl FnOnce() -> bool,
+) -> Option<Graphics> {
+    if !is_terminal {
+        return None;
+    }
+
+    let var = |name: &str| var(name).filter(|value| !value.is_empty());
+
+    let term = var("TERM").unwrap_or_default();
+    let program = var("TERM_PROGRAM").unwrap_or_default();
+
+    if term == "xterm-kitty" || term == "xterm-ghostty" || program == "ghostty" || var("KITTY_WINDOW_ID").is_some() {
+        Some(Graphics::Kitty)
+    } else if program == "iTerm.app" || program == "WezTerm" || var("LC_TERMINAL").as_deref() == Some("iTerm2") {
+        Some(Graphics::Iterm2)
+    } else {
+        sixel().then_some(Graphics::Sixel)
+    }
+}
+
+/// Sends `request` to the terminal and reads back whatever it answers. Every terminal answers the
+/// primary device attributes request, so that's sent afterwards to know when to stop reading: when
+/// its answer arrives, the terminal has either answered `request` or ignored it. `timeout` is only
+/// for terminals that don't even answer that.
+#[cfg(unix)]
+fn query(request: &[u8], timeout: Duration) -> Vec<u8> {
+    use std::fs::OpenOptions;
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::time::Instant;
+
+    let mut reply = Vec::new();
+
+    let Ok(mut tty) = OpenOptions::new().read(true).write(true).open("/dev/tty") else {
+        return reply;
+    };
+
+    let fd = tty.as_raw_fd();
+
+    // The answer has to be read without waiting for a newline, and without it being echoed
+    let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return reply;
+    }
+
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        return reply;
+    }
+
+    let sent = tty.write_all(request).and_then(|_| tty.write_all(b"\x1b[c")).and_then(|_| tty.flush());
+
+    if sent.is_ok() {
+        let deadline = Instant::now() + timeout;
+
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            let mut poll = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+
+            if unsafe { libc::poll(&mut poll, 1, left.as_millis() as libc::c_int) } <= 0 {
+                break;
+            }
+
+            let mut buffer = [0u8; 64];
+
+            match tty.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => reply.extend_from_slice(&buffer[..read]),
+            }
+
+            if parse_attributes_reply(&reply).is_some() {
+                break;
+            }
+        }
+    }
+
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+
+    reply
+}
+
+#[cfg(not(unix))]
+fn query(_request: &[u8], _timeout: Duration) -> Vec<u8> {
+    Vec::new()
+}
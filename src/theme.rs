@@ -0,0 +1,273 @@
+use std::path::Path;
+use anyhow::{anyhow, bail, Context};
+use clap::ValueEnum;
+use crate::colors::{Ansi, Ansi256, Colour, Rgb24, ANSI_COLOURS};
+use crate::terminal::parse_background_reply;
+
+/// What a terminal's colour scheme actually makes the 16 ANSI colours look like (and its background,
+/// if it says). Every terminal has its own idea, so picking the nearest one only works if it's known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// In the order of their escape codes, like [`ANSI_COLOURS`]
+    pub ansi: [Rgb24; 16],
+    pub background: Option<Rgb24>,
+}
+
+/// The themes that are built in, so they can be used by name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BuiltinTheme {
+    /// What apixels assumes when it isn't told, which is the colours of Ubuntu's terminal
+    Ubuntu,
+    Xterm,
+    /// The colours of the VGA text mode, which is what the Linux console uses
+    Vga,
+    SolarizedDark,
+    SolarizedLight,
+    GruvboxDark,
+    GruvboxLight,
+    Dracula,
+    Nord,
+}
+
+/// The colour names Windows Terminal and alacritty use, in the order of the ANSI colours. Windows
+/// Terminal calls magenta purple, and puts `bright` in front of the name for the other 8.
+const COLOUR_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// Turns a list of hex colours into a theme. They're all valid, so this can't fail.
+fn hex_theme(ansi: [u32; 16], background: u32) -> Theme {
+    let rgb = |hex: u32| Rgb24::from(((hex >> 16) as u8, (hex >> 8) as u8, hex as u8));
+
+    Theme {
+        ansi: ansi.map(rgb),
+        background: Some(rgb(background)),
+    }
+}
+
+impl BuiltinTheme {
+    pub fn theme(self) -> Theme {
+        match self {
+            BuiltinTheme::Ubuntu => Theme {
+                ansi: ANSI_COLOURS.map(|colour| Rgb24::from(colour.into_rgb())),
+                background: Some(Rgb24::from((0x30, 0x0a, 0x24))),
+            },
+            BuiltinTheme::Xterm => hex_theme([
+                0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5,
+                0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+            ], 0x000000),
+            BuiltinTheme::Vga => hex_theme([
+                0x000000, 0xaa0000, 0x00aa00, 0xaa5500, 0x0000aa, 0xaa00aa, 0x00aaaa, 0xaaaaaa,
+                0x555555, 0xff5555, 0x55ff55, 0xffff55, 0x5555ff, 0xff55ff, 0x55ffff, 0xffffff,
+            ], 0x000000),
+            // Both Solarized themes use the same 16 colours, just on different backgrounds
+            BuiltinTheme::SolarizedDark => hex_theme(SOLARIZED, 0x002b36),
+            BuiltinTheme::SolarizedLight => hex_theme(SOLARIZED, 0xfdf6e3),
+            BuiltinTheme::GruvboxDark => hex_theme([
+                0x282828, 0xcc241d, 0x98971a, 0xd79921, 0x458588, 0xb16286, 0x689d6a, 0xa89984,
+                0x928374, 0xfb4934, 0xb8bb26, 0xfabd2f, 0x83a598, 0xd3869b, 0x8ec07c, 0xebdbb2,
+            ], 0x282828),
+            BuiltinTheme::GruvboxLight => hex_theme([
+                0xfbf1c7, 0xcc241d, 0x98971a, 0xd79921, 0x458588, 0xb16286, 0x689d6a, 0x7c6f64,
+                0x928374, 0x9d0006, 0x79740e, 0xb57614, 0x076678, 0x8f3f71, 0x427b58, 0x3c3836,
+            ], 0xfbf1c7),
+            BuiltinTheme::Dracula => hex_theme([
+                0x21222c, 0xff5555, 0x50fa7b, 0xf1fa8c, 0xbd93f9, 0xff79c6, 0x8be9fd, 0xf8f8f2,
+                0x6272a4, 0xff6e6e, 0x69ff94, 0xffffa5, 0xd6acff, 0xff92df, 0xa4ffff, 0xffffff,
+            ], 0x282a36),
+            BuiltinTheme::Nord => hex_theme([
+                0x3b4252, 0xbf616a, 0xa3be8c, 0xebcb8b, 0x81a1c1, 0xb48ead, 0x88c0d0, 0xe5e9f0,
+                0x4c566a, 0xbf616a, 0xa3be8c, 0xebcb8b, 0x81a1c1, 0xb48ead, 0x8fbcbb, 0xeceff4,
+            ], 0x2e3440),
+        }
+    }
+}
+
+const SOLARIZED: [u32; 16] = [
+    0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5,
+    0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+];
+
+impl Theme {
+    /// The built in theme called `name`, or else the theme in the file at that path.
+    pub fn named(name: &str) -> anyhow::Result<Theme> {
+        match BuiltinTheme::from_str(name, true) {
+            Ok(builtin) => Ok(builtin.theme()),
+            Err(_) => Theme::load(name),
+        }
+    }
+
+    /// Loads an iTerm2 `.itermcolors` file, a Windows Terminal colour scheme (`.json`), an alacritty
+    /// config (`.toml`) or anything else as X resources, going by the file's extension.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Theme> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path).with_context(|| format!("Couldn't read the theme {}", path.display()))?;
+
+        let theme = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("itermcolors") => Theme::from_itermcolors(&contents),
+            Some("json") => Theme::from_windows_terminal(&String::from_utf8_lossy(&contents)),
+            Some("toml") => Theme::from_alacritty(&String::from_utf8_lossy(&contents)),
+            _ => Theme::from_xresources(&String::from_utf8_lossy(&contents)),
+        };
+
+        theme.with_context(|| format!("{} isn't a theme apixels understands", path.display()))
+    }
+
+    /// Reads an iTerm2 colour preset, which is a property list with a dictionary of colour components
+    /// (from 0 to 1) for each of `Ansi 0 Color` to `Ansi 15 Color`.
+    pub fn from_itermcolors(contents: &[u8]) -> anyhow::Result<Theme> {
+        let plist = plist::Value::from_reader(std::io::Cursor::new(contents))?;
+        let colours = plist.as_dictionary().ok_or_else(|| anyhow!("Expected a dictionary of colours"))?;
+
+        let colour = |key: &str| -> Option<anyhow::Result<Rgb24>> {
+            let components = colours.get(key)?.as_dictionary()?;
+
+            let component = |name: &str| components
+                .get(name)
+                .and_then(|value| value.as_real())
+                .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .ok_or_else(|| anyhow!("{key} has no {name}"));
+
+            Some(component("Red Component").and_then(|r| {
+                Ok(Rgb24::from((r, component("Green Component")?, component("Blue Component")?)))
+            }))
+        };
+
+        Theme::collect(
+            |index| colour(&format!("Ansi {index} Color")),
+            colour("Background Color"),
+        )
+    }
+
+    /// Reads a Windows Terminal colour scheme: either the scheme on its own, or a `settings.json`, in
+    /// which case the first of its schemes is used.
+    pub fn from_windows_terminal(contents: &str) -> anyhow::Result<Theme> {
+        let json: serde_json::Value = serde_json::from_str(contents)?;
+
+        let scheme = match json.get("schemes") {
+            Some(schemes) => schemes.get(0).ok_or_else(|| anyhow!("There aren't any schemes in the settings"))?,
+            None => &json,
+        };
+
+        let colour = |key: &str| scheme.get(key).map(|value| value
+            .as_str()
+            .ok_or_else(|| anyhow!("{key} should be a string"))
+            .and_then(parse_colour));
+
+        Theme::collect(
+            |index| {
+                let name = COLOUR_NAMES[index % 8].replace("magenta", "purple");
+
+                if index < 8 {
+                    colour(&name)
+                } else {
+                    colour(&format!("bright{}{}", name[..1].to_uppercase(), &name[1..]))
+                }
+            },
+            colour("background"),
+        )
+    }
+
+    /// Reads the `[colors.normal]`, `[colors.bright]` and `[colors.primary]` tables out of an alacritty
+    /// config.
+    pub fn from_alacritty(contents: &str) -> anyhow::Result<Theme> {
+        let toml: toml::Table = contents.parse()?;
+
+        let colour = |table: &str, key: &str| toml
+            .get("colors")
+            .and_then(|colours| colours.get(table))
+            .and_then(|colours| colours.get(key))
+            .map(|value| value
+                .as_str()
+                .ok_or_else(|| anyhow!("colors.{table}.{key} should be a string"))
+                .and_then(parse_colour));
+
+        Theme::collect(
+            |index| colour(if index < 8 { "normal" } else { "bright" }, COLOUR_NAMES[index % 8]),
+            colour("primary", "background"),
+        )
+    }
+
+    /// Reads the `color0` to `color15` and `background` resources out of an `.Xresources` file. Any
+    /// prefix (like `*.`, `*` or `URxvt.`) is fine, and so are simple `#define`s.
+    pub fn from_xresources(contents: &str) -> anyhow::Result<Theme> {
+        let mut defines = Vec::new();
+        let mut resources = Vec::new();
+
+        for line in contents.lines().map(str::trim) {
+            if let Some(define) = line.strip_prefix("#define") {
+                if let Some((name, value)) = define.trim().split_once(char::is_whitespace) {
+                    defines.push((name, value.trim()));
+                }
+            } else if let Some((name, value)) = line.split_once(':').filter(|_| !line.starts_with('!')) {
+                let name = name.trim().rsplit(['.', '*']).next().unwrap_or_default();
+
+                resources.push((name, value.trim()));
+            }
+        }
+
+        // Later resources override earlier ones
+        let colour = |key: &str| resources.iter().rev().find(|(name, _)| *name == key).map(|(_, value)| {
+            let value = defines.iter().rev().find(|(name, _)| name == value).map_or(*value, |(_, value)| value);
+
+            parse_colour(value)
+        });
+
+        Theme::collect(|index| colour(&format!("color{index}")), colour("background"))
+    }
+
+    /// Puts the colours together, saying which of them were missing (if any were).
+    fn collect(colour: impl Fn(usize) -> Option<anyhow::Result<Rgb24>>, background: Option<anyhow::Result<Rgb24>>) -> anyhow::Result<Theme> {
+        let mut ansi = [Rgb24::from((0, 0, 0)); 16];
+        let mut missing = Vec::new();
+
+        for (index, slot) in ansi.iter_mut().enumerate() {
+            match colour(index) {
+                Some(colour) => *slot = colour?,
+                None => missing.push(index.to_string()),
+            }
+        }
+
+        if !missing.is_empty() {
+            bail!("The theme is missing ANSI colour {}", missing.join(", "))
+        }
+
+        Ok(Theme {
+            ansi,
+            background: background.transpose()?,
+        })
+    }
+
+    /// The ANSI colour that looks the closest to `colour` with this theme.
+    pub fn nearest_ansi(&self, colour: Rgb24) -> Ansi {
+        let (index, _) = self.ansi
+            .iter()
+            .enumerate()
+            .map(|(index, ansi)| (index, ansi.colour_distance(colour)))
+            .fold((0, f64::INFINITY), |nearest, current| if current.1 < nearest.1 { current } else { nearest });
+
+        ANSI_COLOURS[index]
+    }
+
+    /// The closest of the 256 colour palette, now that the first 16 of them are known as well as the
+    /// colour cube and the greys.
+    pub fn nearest_ansi256(&self, colour: Rgb24) -> Ansi256 {
+        let fixed = Ansi256::from_rgb(colour);
+        let ansi = self.nearest_ansi(colour);
+
+        if self.ansi[ansi as usize].colour_distance(colour) < fixed.colour_distance(colour) {
+            Ansi256 { index: ansi as u8 }
+        } else {
+            fixed
+        }
+    }
+}
+
+/// Parses a colour the way theme files write them: `#rrggbb`, `0xrrggbb` or X11's `rgb:rr/gg/bb`.
+fn parse_colour(value: &str) -> anyhow::Result<Rgb24> {
+    let value = value.trim().trim_matches(['"', '\'']);
+
+    if value.starts_with("rgb:") {
+        parse_background_reply(value.as_bytes()).ok_or_else(|| anyhow!("`{value}` is not a valid colour"))
+    } else {
+        value.strip_prefix("0x").unwrap_or(value).parse()
+    }
+}
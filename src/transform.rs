@@ -0,0 +1,222 @@
+use std::io::Cursor;
+use std::str::FromStr;
+use anyhow::{anyhow, bail};
+use clap::ValueEnum;
+use image::{imageops, Rgba, RgbaImage};
+
+/// A position or size along one side of the image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Length {
+    Pixels(u32),
+    /// From 0 to 100
+    Percent(f32),
+}
+
+impl Length {
+    fn resolve(self, side: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => pixels,
+            Length::Percent(percent) => (side as f32 * percent / 100.0).round() as u32,
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        match s.strip_suffix('%') {
+            Some(percent) => {
+                let percent: f32 = percent.parse().map_err(|_| anyhow!("`{s}` is not a valid percentage"))?;
+
+                if !(0.0..=100.0).contains(&percent) {
+                    bail!("`{s}` is not between 0% and 100%")
+                }
+
+                Ok(Length::Percent(percent))
+            }
+            None => Ok(Length::Pixels(s.parse().map_err(|_| anyhow!("`{s}` is not a valid number of pixels"))?)),
+        }
+    }
+}
+
+/// A rectangle of the image, which can be given in pixels or as a percentage of the image size
+/// (or a mix of both).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Region {
+    pub x: Length,
+    pub y: Length,
+    pub width: Length,
+    pub height: Length,
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    /// Parses `x,y,width,height`, e.g. `10,20,300,200` or `25%,25%,50%,50%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(',').map(Length::from_str).collect::<anyhow::Result<Vec<_>>>()?;
+
+        let [x, y, width, height] = parts[..] else {
+            bail!("Expected a region in the form x,y,width,height, got `{s}`")
+        };
+
+        Ok(Region { x, y, width, height })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Flip {
+    Horizontal,
+    Vertical,
+}
+
+/// Changes to the shape of the image. Rotations are clockwise.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transform {
+    /// Anything outside of the image is left out. The crop is always at least one pixel big.
+    Crop(Region),
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Rotation by any angle, in degrees. The image is made big enough to fit the rotated image in
+    /// and the corners that are left over are transparent.
+    Rotate(f32),
+    Flip(Flip),
+    /// Shrinks or stretches the image to be as big as it can be inside `width` × `height`, without
+    /// changing its aspect ratio.
+    Fit(u32, u32),
+}
+
+impl Transform {
+    /// Rotations by a multiple of 90 degrees don't need any resampling, so they get turned into
+    /// one of the exact rotations.
+    pub fn rotate(degrees: f32) -> Option<Transform> {
+        let degrees = degrees.rem_euclid(360.0);
+
+        match degrees {
+            _ if degrees == 0.0 => None,
+            _ if degrees == 90.0 => Some(Transform::Rotate90),
+            _ if degrees == 180.0 => Some(Transform::Rotate180),
+            _ if degrees == 270.0 => Some(Transform::Rotate270),
+            _ => Some(Transform::Rotate(degrees)),
+        }
+    }
+
+    pub fn apply(&self, img: &RgbaImage) -> RgbaImage {
+        match *self {
+            Transform::Crop(region) => {
+                let (width, height) = img.dimensions();
+
+                // There's no pixel to keep
+                if width == 0 || height == 0 {
+                    return img.clone();
+                }
+
+                let x = region.x.resolve(width).min(width.saturating_sub(1));
+                let y = region.y.resolve(height).min(height.saturating_sub(1));
+
+                let crop_width = region.width.resolve(width).clamp(1, width - x);
+                let crop_height = region.height.resolve(height).clamp(1, height - y);
+
+                imageops::crop_imm(img, x, y, crop_width, crop_height).to_image()
+            }
+            Transform::Rotate90 => imageops::rotate90(img),
+            Transform::Rotate180 => imageops::rotate180(img),
+            Transform::Rotate270 => imageops::rotate270(img),
+            Transform::Rotate(degrees) => rotate(img, degrees),
+            Transform::Flip(Flip::Horizontal) => imageops::flip_horizontal(img),
+            Transform::Flip(Flip::Vertical) => imageops::flip_vertical(img),
+            Transform::Fit(width, height) => {
+                let scale = (width as f32 / img.width() as f32).min(height as f32 / img.height() as f32);
+
+                let fit_width = ((img.width() as f32 * scale).round() as u32).clamp(1, width.max(1));
+                let fit_height = ((img.height() as f32 * scale).round() as u32).clamp(1, height.max(1));
+
+                imageops::resize(img, fit_width, fit_height, imageops::FilterType::Triangle)
+            }
+        }
+    }
+}
+
+fn rotate(img: &RgbaImage, degrees: f32) -> RgbaImage {
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+
+    let new_width = (width * cos.abs() + height * sin.abs()).round().max(1.0);
+    let new_height = (width * sin.abs() + height * cos.abs()).round().max(1.0);
+
+    RgbaImage::from_fn(new_width as u32, new_height as u32, |x, y| {
+        // Work out where this pixel came from by rotating it back the other way around the centre
+        let dx = x as f32 + 0.5 - new_width / 2.0;
+        let dy = y as f32 + 0.5 - new_height / 2.0;
+
+        let source_x = dx * cos + dy * sin + width / 2.0 - 0.5;
+        let source_y = -dx * sin + dy * cos + height / 2.0 - 0.5;
+
+        bilinear(img, source_x, source_y)
+    })
+}
+
+/// Bilinear sampling where everything outside of the image is transparent. The colours are weighted
+/// by their alpha so that the transparent surroundings don't darken the edges.
+fn bilinear(img: &RgbaImage, x: f32, y: f32) -> Rgba<u8> {
+    let (left, top) = (x.floor(), y.floor());
+    let (x_weight, y_weight) = (x - left, y - top);
+
+    let mut colour = [0f32; 3];
+    let mut alpha = 0f32;
+
+    for (offset_x, offset_y, weight) in [
+        (0, 0, (1.0 - x_weight) * (1.0 - y_weight)),
+        (1, 0, x_weight * (1.0 - y_weight)),
+        (0, 1, (1.0 - x_weight) * y_weight),
+        (1, 1, x_weight * y_weight),
+    ] {
+        let (pixel_x, pixel_y) = (left as i64 + offset_x, top as i64 + offset_y);
+
+        if pixel_x < 0 || pixel_y < 0 || pixel_x >= img.width() as i64 || pixel_y >= img.height() as i64 {
+            continue;
+        }
+
+        let Rgba([r, g, b, a]) = *img.get_pixel(pixel_x as u32, pixel_y as u32);
+        let weight = weight * a as f32;
+
+        colour[0] += r as f32 * weight;
+        colour[1] += g as f32 * weight;
+        colour[2] += b as f32 * weight;
+        alpha += weight;
+    }
+
+    if alpha == 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    let [r, g, b] = colour.map(|channel| (channel / alpha).round() as u8);
+
+    Rgba([r, g, b, alpha.round().min(255.0) as u8])
+}
+
+/// The transforms needed to turn an image the right way up, going by its EXIF orientation tag.
+/// Images without EXIF data (or that aren't in a format that has it) don't need any.
+pub fn orientation(bytes: &[u8]) -> Vec<Transform> {
+    let orientation = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .ok()
+        .and_then(|exif| exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?.value.get_uint(0));
+
+    match orientation {
+        Some(2) => vec![Transform::Flip(Flip::Horizontal)],
+        Some(3) => vec![Transform::Rotate180],
+        Some(4) => vec![Transform::Flip(Flip::Vertical)],
+        // Transpose
+        Some(5) => vec![Transform::Rotate90, Transform::Flip(Flip::Horizontal)],
+        Some(6) => vec![Transform::Rotate90],
+        // Transverse
+        Some(7) => vec![Transform::Rotate270, Transform::Flip(Flip::Horizontal)],
+        Some(8) => vec![Transform::Rotate270],
+        _ => vec![],
+    }
+}
@@ -0,0 +1,106 @@
+use apixels::adaptive::{split, SubCell};
+use apixels::colors::Rgb24;
+use apixels::dominant::AlphaMode;
+use apixels::linear::Blending;
+use color_thief::Color as CtColor;
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+/// A `width`×`height` sample, with each pixel coloured by `colour(x, y)`.
+fn sample(width: u32, height: u32, colour: impl Fn(u32, u32) -> [u8; 4]) -> Vec<u8> {
+    let colour = &colour;
+
+    (0..height).flat_map(|y| (0..width).flat_map(move |x| colour(x, y))).collect()
+}
+
+fn split_opaque(pixels: &[u8], size: (u32, u32), sub_cell: SubCell) -> Option<(u8, [CtColor; 2])> {
+    split(pixels, size, sub_cell, (AlphaMode::Skip, Rgb24::from((0, 0, 0))), Blending::Srgb)
+}
+
+#[test]
+fn quadrant_bits_go_left_to_right_top_to_bottom() {
+    assert_eq!(SubCell::Quadrant.glyph(0b0000), ' ');
+    assert_eq!(SubCell::Quadrant.glyph(0b0001), '▘');
+    assert_eq!(SubCell::Quadrant.glyph(0b0010), '▝');
+    assert_eq!(SubCell::Quadrant.glyph(0b0100), '▖');
+    assert_eq!(SubCell::Quadrant.glyph(0b1000), '▗');
+    assert_eq!(SubCell::Quadrant.glyph(0b1001), '▚');
+    assert_eq!(SubCell::Quadrant.glyph(0b1100), '▄');
+    assert_eq!(SubCell::Quadrant.glyph(0b1111), SubCell::Quadrant.full());
+}
+
+#[test]
+fn braille_bits_become_the_right_dots() {
+    assert_eq!(SubCell::Braille.glyph(0), '⠀');
+    // The top row is dots 1 and 4, and the bottom row is dots 7 and 8
+    assert_eq!(SubCell::Braille.glyph(0b0000_0001), '⠁');
+    assert_eq!(SubCell::Braille.glyph(0b0000_0010), '⠈');
+    assert_eq!(SubCell::Braille.glyph(0b0101_0101), '⡇');
+    assert_eq!(SubCell::Braille.glyph(0b1100_0000), '⣀');
+    assert_eq!(SubCell::Braille.glyph(0b1111_1111), SubCell::Braille.full());
+}
+
+#[test]
+fn masks_come_back_out_of_glyphs() {
+    for mask in 0..16 {
+        assert_eq!(SubCell::Quadrant.mask(SubCell::Quadrant.glyph(mask)), Some(mask));
+    }
+
+    for mask in 0..=255 {
+        assert_eq!(SubCell::Braille.mask(SubCell::Braille.glyph(mask)), Some(mask));
+    }
+
+    assert_eq!(SubCell::Quadrant.mask('x'), None);
+}
+
+#[test]
+fn one_odd_quadrant_out() {
+    // A 4×4 sample that's blue apart from a red top left corner
+    let pixels = sample(4, 4, |x, y| if x < 2 && y < 2 { RED } else { BLUE });
+
+    let (mask, colours) = split_opaque(&pixels, (4, 4), SubCell::Quadrant).unwrap();
+
+    // Blue covers the most, so it comes first and the red corner is what gets drawn
+    assert_eq!(SubCell::Quadrant.glyph(mask), '▘');
+    assert_eq!(colours, [CtColor::new(0, 0, 255), CtColor::new(255, 0, 0)]);
+}
+
+#[test]
+fn diagonal_quadrants() {
+    let pixels = sample(2, 2, |x, y| if x == y { WHITE } else { BLACK });
+
+    // Two of each is a tie, so the darker colour (which k-means starts with) comes first
+    let (mask, colours) = split_opaque(&pixels, (2, 2), SubCell::Quadrant).unwrap();
+
+    assert_eq!(SubCell::Quadrant.glyph(mask), '▚');
+    assert_eq!(colours, [CtColor::new(0, 0, 0), CtColor::new(255, 255, 255)]);
+}
+
+#[test]
+fn braille_bottom_row() {
+    // A 4×8 sample that's black apart from the bottom two rows of pixels
+    let pixels = sample(4, 8, |_, y| if y >= 6 { WHITE } else { BLACK });
+
+    let (mask, colours) = split_opaque(&pixels, (4, 8), SubCell::Braille).unwrap();
+
+    assert_eq!(SubCell::Braille.glyph(mask), '⣀');
+    assert_eq!(colours, [CtColor::new(0, 0, 0), CtColor::new(255, 255, 255)]);
+}
+
+#[test]
+fn flat_samples_dont_split() {
+    let pixels = sample(4, 8, |_, _| RED);
+
+    assert_eq!(split_opaque(&pixels, (4, 4), SubCell::Quadrant), None);
+    assert_eq!(split_opaque(&pixels, (4, 8), SubCell::Braille), None);
+}
+
+#[test]
+fn skipped_parts_dont_split() {
+    let pixels = sample(2, 2, |x, y| if (x, y) == (0, 0) { [0, 0, 0, 0] } else if x == 1 { RED } else { BLUE });
+
+    assert_eq!(split_opaque(&pixels, (2, 2), SubCell::Quadrant), None);
+}
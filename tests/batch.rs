@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+use apixels::batch::{expand, matches, output_path, output_paths, Input};
+
+/// A directory of empty files to search, made fresh for each test.
+fn tree(name: &str, files: &[&str]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("apixels-batch-{name}-{}", std::process::id()));
+    std::fs::remove_dir_all(&root).ok();
+
+    for file in files {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+
+    root
+}
+
+fn found(inputs: &[Input], root: &Path) -> Vec<String> {
+    inputs.iter().map(|input| input.path.strip_prefix(root).unwrap().display().to_string()).collect()
+}
+
+#[test]
+fn wildcards() {
+    assert!(matches("*.png", "ladybug.png"));
+    assert!(matches("*.png", ".png"));
+    assert!(!matches("*.png", "ladybug.jpg"));
+    assert!(matches("lady?ug.*", "ladybug.jpg"));
+    assert!(matches("*bug*", "ladybug.jpg"));
+    assert!(matches("a*b*c", "aXXbYYbZc"));
+    assert!(!matches("a*b*c", "aXXbYYbZ"));
+    assert!(matches("[a-c]*", "banana"));
+    assert!(!matches("[!a-c]*", "banana"));
+    assert!(matches("[]]", "]"));
+    assert!(matches("[", "["));
+}
+
+#[test]
+fn directories_only_give_images() {
+    let root = tree("directories", &["b.png", "a.JPG", "notes.md", "art.ans", "sub/c.png"]);
+
+    let inputs = expand(&[root.display().to_string()], false).unwrap();
+    assert_eq!(found(&inputs, &root), ["a.JPG", "art.ans", "b.png"]);
+
+    let inputs = expand(&[root.display().to_string()], true).unwrap();
+    assert_eq!(found(&inputs, &root), ["a.JPG", "art.ans", "b.png", "sub/c.png"]);
+    assert_eq!(inputs[3].directory, Path::new("sub"));
+}
+
+#[test]
+fn patterns_are_matched_like_the_shell_would() {
+    let root = tree("patterns", &["a.png", "b.jpg", ".hidden.png", "one/c.png", "one/two/d.png", "three/e.jpg"]);
+
+    let pattern = |pattern: &str| found(&expand(&[root.join(pattern).display().to_string()], false).unwrap(), &root);
+
+    assert_eq!(pattern("*.png"), ["a.png"]);
+    assert_eq!(pattern("*/*.png"), ["one/c.png"]);
+    assert_eq!(pattern("**/*.png"), ["a.png", "one/c.png", "one/two/d.png"]);
+    assert_eq!(pattern("t*/*"), ["three/e.jpg"]);
+    assert!(expand(&[root.join("*.gif").display().to_string()], false).is_err());
+}
+
+#[test]
+fn plain_files_are_taken_as_they_are() {
+    let inputs = expand(&["missing.png".to_string()], false).unwrap();
+
+    assert_eq!(inputs, [Input { path: PathBuf::from("missing.png"), directory: PathBuf::new() }]);
+}
+
+#[test]
+fn outputs_follow_the_template() {
+    let input = Input {
+        path: PathBuf::from("art/sub/ladybug.jpg"),
+        directory: PathBuf::from("sub"),
+    };
+
+    assert_eq!(output_path(&input, "{stem}.{ext}", "html", Path::new("out")), Path::new("out/sub/ladybug.html"));
+    assert_eq!(output_path(&input, "{name}-{stem}.{ext}", "ans", Path::new("out")), Path::new("out/sub/ladybug.jpg-ladybug.ans"));
+}
+
+#[test]
+fn inputs_cant_share_an_output() {
+    let root = tree("clashes", &["logo.png", "logo.bmp", "other.png"]);
+    let inputs = expand(&[root.display().to_string()], false).unwrap();
+
+    let error = output_paths(&inputs, "{stem}.{ext}", "txt", Path::new("out")).unwrap_err().to_string();
+
+    assert!(error.contains("logo.bmp") && error.contains("logo.png") && error.contains("{name}"), "{error}");
+
+    let paths = output_paths(&inputs, "{name}.{ext}", "txt", Path::new("out")).unwrap();
+
+    assert_eq!(paths, [Path::new("out/logo.bmp.txt"), Path::new("out/logo.png.txt"), Path::new("out/other.png.txt")]);
+}
@@ -0,0 +1,57 @@
+use apixels::chat::{bbcode, discord, irc};
+use apixels::grid::Grid;
+use apixels::theme::BuiltinTheme;
+
+fn parse(text: &str) -> Grid {
+    Grid::parse(text, &BuiltinTheme::Ubuntu.theme(), None)
+}
+
+fn written(write: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> String {
+    let mut output = Vec::new();
+    write(&mut output).unwrap();
+
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn irc_codes_only_change_with_the_colours() {
+    let grid = parse("\x1b[38;2;255;0;0mab \x1b[38;2;0;0;0m \x1b[38;2;255;0;0mc\x1b[48;2;255;255;0md\x1b[0me  ");
+
+    assert_eq!(written(|output| irc(&grid, 16, output)), "\x0304ab  c\x0304,08d\x0fe\n");
+}
+
+#[test]
+fn irc_codes_are_always_two_digits() {
+    let grid = parse("\x1b[38;2;0;0;0m1\x1b[38;2;0;0;127m,2");
+
+    assert_eq!(written(|output| irc(&grid, 16, output)), "\x03011\x0302\x02\x02,2\n");
+}
+
+#[test]
+fn irc_has_99_colours_if_asked() {
+    let grid = parse("\x1b[38;2;116;58;0mx");
+
+    assert_eq!(written(|output| irc(&grid, 99, output)), "\x0329x\n");
+    assert_eq!(written(|output| irc(&grid, 16, output)), "\x0305x\n");
+}
+
+#[test]
+fn irc_backgrounds_without_foregrounds_use_the_default() {
+    let grid = parse("\x1b[48;2;0;0;0m x");
+
+    assert_eq!(written(|output| irc(&grid, 16, output)), "\x0399,01 x\n");
+}
+
+#[test]
+fn discord_gets_its_own_eight_colours() {
+    let grid = parse("\x1b[38;2;220;50;47;48;2;0;43;54mab\x1b[0m\ncd");
+
+    assert_eq!(written(|output| discord(&grid, output)), "```ansi\n\x1b[0;31;40mab\n\x1b[0mcd\n```\n");
+}
+
+#[test]
+fn bbcode_turns_backgrounds_into_blocks() {
+    let grid = parse("\x1b[38;2;255;0;0ma\x1b[48;2;255;0;0m \x1b[0m b");
+
+    assert_eq!(written(|output| bbcode(&grid, output)), "[color=#ff0000]a█ [/color]b\n");
+}
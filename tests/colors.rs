@@ -0,0 +1,178 @@
+use apixels::colors::{expand, quantise, Ansi, Ansi256, Colour, Monochrome, Rgb24, Rgb332, Rgb444, Rgb555, Rgb565, Rgb565Layout};
+use apixels::luma::Luma;
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+fn rgb() -> impl Strategy<Value = (u8, u8, u8)> {
+    (any::<u8>(), any::<u8>(), any::<u8>())
+}
+
+/// Quantising a colour that has already been quantised shouldn't change it.
+fn idempotent<C: Colour>(colour: (u8, u8, u8)) -> Result<(), TestCaseError> {
+    let once = C::from_rgb8(colour).into_rgb();
+    let twice = C::from_rgb8(once).into_rgb();
+
+    prop_assert_eq!(once, twice, "{:?} quantised to {:?} the first time and {:?} the second", colour, once, twice);
+
+    Ok(())
+}
+
+/// How far each channel moved on the way through `C`.
+fn error<C: Colour>(colour: (u8, u8, u8)) -> [u8; 3] {
+    let (r, g, b) = C::from_rgb8(colour).into_rgb();
+
+    [r.abs_diff(colour.0), g.abs_diff(colour.1), b.abs_diff(colour.2)]
+}
+
+proptest! {
+    #[test]
+    fn every_colour_is_idempotent(colour in rgb()) {
+        idempotent::<Rgb24>(colour)?;
+        idempotent::<Rgb565>(colour)?;
+        idempotent::<Rgb555>(colour)?;
+        idempotent::<Rgb444>(colour)?;
+        idempotent::<Rgb332>(colour)?;
+        idempotent::<Monochrome>(colour)?;
+        idempotent::<Ansi>(colour)?;
+        idempotent::<Ansi256>(colour)?;
+        idempotent::<(u8, u8, u8)>(colour)?;
+        idempotent::<color_thief::Color>(colour)?;
+    }
+
+    #[test]
+    fn lossless_colours_round_trip(colour in rgb()) {
+        prop_assert_eq!(error::<Rgb24>(colour), [0; 3]);
+        prop_assert_eq!(error::<(u8, u8, u8)>(colour), [0; 3]);
+        prop_assert_eq!(error::<color_thief::Color>(colour), [0; 3]);
+    }
+
+    #[test]
+    fn rgb565_error_is_at_most_half_a_step(colour in rgb()) {
+        // 255 / 31 / 2 and 255 / 63 / 2, rounded down
+        let [r, g, b] = error::<Rgb565>(colour);
+
+        prop_assert!(r <= 4 && g <= 2 && b <= 4, "{:?} was off by {:?}", colour, [r, g, b]);
+    }
+
+    #[test]
+    fn smaller_depths_are_at_most_half_a_step_out(colour in rgb()) {
+        // Half of 255 / (2^bits - 1), rounded up
+        let half_step = |bits: u32| (255 / ((1 << bits) - 1) / 2) as u8 + 1;
+
+        for ([r, g, b], bits) in [
+            (error::<Rgb555>(colour), [5, 5, 5]),
+            (error::<Rgb444>(colour), [4, 4, 4]),
+            (error::<Rgb332>(colour), [3, 3, 2]),
+        ] {
+            prop_assert!(r <= half_step(bits[0]) && g <= half_step(bits[1]) && b <= half_step(bits[2]), "{:?} was off by {:?}", colour, [r, g, b]);
+        }
+    }
+
+    #[test]
+    fn grey_levels_are_idempotent(levels in 2u16..=256, colour in rgb()) {
+        let once = Monochrome::with_levels(Rgb24::from(colour), Luma::default(), levels);
+        let twice = Monochrome::with_levels(Rgb24::from(once.into_rgb()), Luma::default(), levels);
+
+        prop_assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn grey_levels_keep_black_and_white(levels in 2u16..=256) {
+        prop_assert_eq!(Monochrome::with_levels(Rgb24::from((0, 0, 0)), Luma::default(), levels).intensity, 0);
+        prop_assert_eq!(Monochrome::with_levels(Rgb24::from((255, 255, 255)), Luma::default(), levels).intensity, 255);
+    }
+
+    #[test]
+    fn rgb565_levels_are_in_range(colour in rgb()) {
+        for layout in [Rgb565Layout::Rgb, Rgb565Layout::Bgr] {
+            let (r, g, b) = Rgb565::with_layout(Rgb24::from(colour), layout).channels();
+
+            prop_assert!(r <= 31 && g <= 63 && b <= 31);
+        }
+    }
+
+    #[test]
+    fn rgb565_layouts_agree(colour in rgb()) {
+        let rgb = Rgb565::with_layout(Rgb24::from(colour), Rgb565Layout::Rgb);
+        let bgr = Rgb565::with_layout(Rgb24::from(colour), Rgb565Layout::Bgr);
+
+        prop_assert_eq!(rgb.into_rgb(), bgr.into_rgb());
+        prop_assert_eq!(rgb.to_layout(Rgb565Layout::Bgr), bgr);
+        prop_assert_eq!(bgr.to_layout(Rgb565Layout::Rgb), rgb);
+    }
+
+    #[test]
+    fn monochrome_is_grey_and_in_between(colour in rgb()) {
+        let (r, g, b) = Monochrome::from_rgb8(colour).into_rgb();
+        let (min, max) = (colour.0.min(colour.1).min(colour.2), colour.0.max(colour.1).max(colour.2));
+
+        prop_assert!(r == g && g == b);
+        prop_assert!((min..=max).contains(&r));
+    }
+
+    #[test]
+    fn monochrome_keeps_greys(grey in any::<u8>()) {
+        prop_assert_eq!(error::<Monochrome>((grey, grey, grey)), [0; 3]);
+    }
+
+    #[test]
+    fn quantise_and_expand_round_trip_levels(bits in 1u32..=8, channel in any::<u8>()) {
+        let level = quantise(channel, bits);
+
+        prop_assert!(level < 1 << bits);
+        prop_assert_eq!(quantise(expand(level, bits), bits), level);
+
+        // Never more than half a step out
+        prop_assert!(expand(level, bits).abs_diff(channel) as u32 * 2 * ((1 << bits) - 1) <= 255);
+    }
+}
+
+#[test]
+fn rgb565_layouts_put_red_at_opposite_ends() {
+    let red = Rgb24::from((255, 0, 0));
+
+    assert_eq!(Rgb565::with_layout(red, Rgb565Layout::Rgb).rgb, 0xf800);
+    assert_eq!(Rgb565::with_layout(red, Rgb565Layout::Bgr).rgb, 0x001f);
+}
+
+#[test]
+fn rgb565_keeps_black_and_white() {
+    assert_eq!(Rgb565::from_rgb8((255, 255, 255)).into_rgb(), (255, 255, 255));
+    assert_eq!(Rgb565::from_rgb8((0, 0, 0)).into_rgb(), (0, 0, 0));
+}
+
+#[test]
+fn grey_levels_use_every_level() {
+    for levels in [2, 4, 16, 100, 256] {
+        let mut shades: Vec<u8> = (0..=255).map(|grey| Monochrome::with_levels(Rgb24::from((grey, grey, grey)), Luma::default(), levels).intensity).collect();
+        shades.dedup();
+
+        assert_eq!(shades.len(), levels as usize, "{levels} levels gave {shades:?}");
+    }
+}
+
+#[test]
+fn ansi256_keeps_its_own_palette() {
+    // The first 16 are never picked, since they're different on every terminal
+    for index in 16..=255 {
+        let colour = Ansi256 { index };
+
+        assert_eq!(Ansi256::from_rgb8(colour.into_rgb()), colour);
+    }
+}
+
+#[test]
+fn ansi256_palette() {
+    assert_eq!(Ansi256 { index: 16 }.into_rgb(), (0, 0, 0));
+    assert_eq!(Ansi256 { index: 196 }.into_rgb(), (255, 0, 0));
+    assert_eq!(Ansi256 { index: 231 }.into_rgb(), (255, 255, 255));
+    assert_eq!(Ansi256 { index: 232 }.into_rgb(), (8, 8, 8));
+    assert_eq!(Ansi256 { index: 255 }.into_rgb(), (238, 238, 238));
+}
+
+#[test]
+fn ansi_keeps_its_own_palette() {
+    for colour in [Ansi::Black, Ansi::DarkRed, Ansi::DarkCyan, Ansi::Gray, Ansi::Purple, Ansi::White] {
+        assert_eq!(Ansi::from_rgb8(colour.into_rgb()), colour);
+    }
+}
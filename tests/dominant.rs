@@ -0,0 +1,139 @@
+use std::io::Cursor;
+use apixels::colors::Rgb24;
+use apixels::dominant::{coverage, flatten, normalise, pick, AlphaMode};
+use apixels::edges::EdgeResolution;
+use apixels::linear::Blending;
+use apixels::luma::Luma as LumaWeights;
+use apixels::picker::Picker;
+use apixels::preprocess::Pipeline;
+use apixels::{into_ascii_controlled, ColourDepth, Ramp};
+use color_thief::Color as CtColor;
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat, Luma, Rgba, RgbaImage};
+
+const MATTE: (u8, u8, u8) = (0, 0, 255);
+
+/// Four pixels of red, with alpha from `alphas`.
+fn red(alphas: [u8; 4]) -> Vec<u8> {
+    alphas.iter().flat_map(|alpha| [255, 0, 0, *alpha]).collect()
+}
+
+fn mean(pixels: &[u8], alpha: AlphaMode) -> Option<[u8; 3]> {
+    pick(pixels, alpha, Rgb24::from(MATTE), Picker::Mean.picker(), Blending::Srgb, None)
+        .map(|[CtColor { r, g, b }, _]| [r, g, b])
+}
+
+#[test]
+fn skipping_drops_transparent_pixels_and_keeps_the_rest() {
+    let flattened = flatten(&red([0, 128, 255, 1]), AlphaMode::Skip, Rgb24::from(MATTE));
+
+    assert_eq!(flattened, [[255, 0, 0]; 3]);
+}
+
+#[test]
+fn compositing_blends_onto_the_matte() {
+    for alpha in [AlphaMode::Composite, AlphaMode::Empty] {
+        let flattened = flatten(&red([0, 128, 255, 51]), alpha, Rgb24::from(MATTE));
+
+        assert_eq!(flattened, [[0, 0, 255], [128, 0, 127], [255, 0, 0], [51, 0, 204]], "{alpha:?}");
+    }
+}
+
+#[test]
+fn fully_transparent_samples() {
+    let pixels = red([0; 4]);
+
+    assert_eq!(coverage(&pixels), 0.0);
+    assert_eq!(mean(&pixels, AlphaMode::Skip), None);
+    assert_eq!(mean(&pixels, AlphaMode::Composite), Some([0, 0, 255]));
+    assert_eq!(mean(&pixels, AlphaMode::Empty), Some([0, 0, 255]));
+}
+
+#[test]
+fn half_covered_samples() {
+    let pixels = red([255, 0, 255, 0]);
+
+    assert_eq!(coverage(&pixels), 0.5);
+    assert_eq!(mean(&pixels, AlphaMode::Skip), Some([255, 0, 0]));
+    assert_eq!(mean(&pixels, AlphaMode::Composite), Some([128, 0, 128]));
+    assert_eq!(mean(&pixels, AlphaMode::Empty), Some([128, 0, 128]));
+}
+
+#[test]
+fn opaque_samples() {
+    let pixels = red([255; 4]);
+
+    assert_eq!(coverage(&pixels), 1.0);
+
+    for alpha in [AlphaMode::Skip, AlphaMode::Composite, AlphaMode::Empty] {
+        assert_eq!(mean(&pixels, alpha), Some([255, 0, 0]), "{alpha:?}");
+    }
+}
+
+#[test]
+fn nothing_is_not_covered() {
+    assert_eq!(coverage(&[]), 0.0);
+}
+
+#[test]
+fn every_image_becomes_rgba8() {
+    let grey: ImageBuffer<Luma<u16>, Vec<u16>> = ImageBuffer::from_pixel(2, 1, Luma([0xffff]));
+    let rgba = normalise(DynamicImage::ImageLuma16(grey));
+
+    assert_eq!(rgba.dimensions(), (2, 1));
+    assert_eq!(rgba.get_pixel(1, 0), &Rgba([255, 255, 255, 255]));
+
+    let float = DynamicImage::ImageRgba32F(ImageBuffer::from_pixel(1, 1, Rgba([1.0, 0.0, 0.5, 0.0])));
+
+    assert_eq!(normalise(float).get_pixel(0, 0), &Rgba([255, 0, 128, 0]));
+}
+
+/// Renders a 6×2 image as three 2×2 cells (fully opaque, half covered and fully transparent) and
+/// says which of them came out coloured rather than blank.
+fn coloured_cells(alpha: AlphaMode, alpha_threshold: f32) -> Vec<bool> {
+    let img = RgbaImage::from_fn(6, 2, |x, y| {
+        let opaque = x < 2 || (x < 4 && y == 0);
+
+        Rgba([255, 0, 0, if opaque { 255 } else { 0 }])
+    });
+
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+
+    let mut output = Vec::new();
+
+    into_ascii_controlled(
+        png,
+        (3.0, 3.0, EdgeResolution::Full),
+        (2, 2),
+        (false, None),
+        false,
+        Ramp::Ascii,
+        (ColourDepth::Rgb24, 256, None),
+        (alpha, Rgb24::from(MATTE), alpha_threshold),
+        Picker::Mean.picker(),
+        (Blending::Srgb, LumaWeights::default()),
+        &Pipeline::new(),
+        None,
+        1 << 30,
+        &mut output,
+    )
+    .unwrap();
+
+    String::from_utf8(output).unwrap().split_terminator(['\0', '\n']).map(|cell| cell.contains('\x1b')).collect()
+}
+
+#[test]
+fn empty_leaves_cells_under_the_threshold_blank() {
+    assert_eq!(coloured_cells(AlphaMode::Empty, 0.0), [true, true, true]);
+    assert_eq!(coloured_cells(AlphaMode::Empty, 0.4), [true, true, false]);
+    assert_eq!(coloured_cells(AlphaMode::Empty, 0.5), [true, true, false]);
+    assert_eq!(coloured_cells(AlphaMode::Empty, 0.6), [true, false, false]);
+    assert_eq!(coloured_cells(AlphaMode::Empty, 1.0), [true, false, false]);
+}
+
+#[test]
+fn only_empty_uses_the_threshold() {
+    assert_eq!(coloured_cells(AlphaMode::Composite, 1.0), [true, true, true]);
+    // Skipping leaves nothing to pick from in the transparent cell, whatever the threshold is
+    assert_eq!(coloured_cells(AlphaMode::Skip, 1.0), [true, true, false]);
+}
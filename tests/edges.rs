@@ -0,0 +1,97 @@
+use apixels::edges::{difference_of_gaussians, overlap, EdgeResolution, Plane};
+use apixels::linear::Blending;
+use apixels::luma::Luma;
+
+/// A single row that's all zeros apart from a one in the middle.
+fn impulse(width: u32) -> Plane {
+    let mut values = vec![0.0; width as usize];
+    values[width as usize / 2] = 1.0;
+
+    Plane { width, height: 1, values }
+}
+
+/// Something with edges all over it, so that the blurs have something to do.
+fn checkerboard(width: u32, height: u32) -> Vec<u8> {
+    (0..width * height)
+        .flat_map(|idx| {
+            let (x, y) = (idx % width, idx / width);
+            let value = if (x / 3 + y / 5) % 2 == 0 { (x * 7 % 256) as u8 } else { 255 - (y * 11 % 256) as u8 };
+
+            [value, value / 2, 255 - value, 255]
+        })
+        .collect()
+}
+
+#[test]
+fn blurs_spread_as_far_as_sigma_says() {
+    for sigma in [1.0f32, 2.0, 3.0, 4.5, 9.0, 20.0] {
+        let blurred = impulse(401).blur(sigma, 0.0);
+
+        let total: f32 = blurred.values.iter().sum();
+        let variance: f32 = blurred.values.iter().enumerate().map(|(x, value)| (x as f32 - 200.0).powi(2) * value).sum::<f32>() / total;
+
+        // Three box blurs of widths w and w + 2 can't hit sigma² exactly, but swapping one box for the
+        // other changes the variance by (4w + 4) / 12, so rounding gets within half of that (and w is
+        // no bigger than the ideal width)
+        let ideal_width = (4.0 * sigma * sigma + 1.0).sqrt();
+        let tolerance = (ideal_width + 1.0) / 6.0;
+
+        assert!((total - 1.0).abs() < 1e-4, "sigma {sigma} doesn't keep the total: {total}");
+        assert!((variance - sigma * sigma).abs() <= tolerance, "sigma {sigma} gave a variance of {variance}");
+    }
+}
+
+#[test]
+fn no_sigma_no_blur() {
+    assert_eq!(impulse(9).blur(0.0, 0.0), impulse(9));
+}
+
+#[test]
+fn blurring_a_constant_leaves_it_alone() {
+    let plane = Plane { width: 37, height: 23, values: vec![0.37; 37 * 23] };
+
+    for (sigma_x, sigma_y) in [(1.0, 1.0), (2.5, 4.0), (30.0, 0.5)] {
+        let blurred = plane.blur(sigma_x, sigma_y);
+
+        assert_eq!((blurred.width, blurred.height), (37, 23));
+        assert!(blurred.values.iter().all(|value| (value - 0.37).abs() < 1e-6), "{sigma_x}, {sigma_y}");
+    }
+}
+
+#[test]
+fn blurs_dont_reach_past_the_overlap() {
+    for (sigma, scalar) in [(1.0, 3.0), (3.0, 3.0), (2.0, 5.0)] {
+        let reach = overlap((sigma, scalar, EdgeResolution::Full), 1) as usize;
+
+        // The wider of the two blurs is the one that reaches furthest
+        let blurred = impulse(401).blur(sigma * scalar, 0.0);
+        let last = blurred.values.iter().rposition(|value| *value != 0.0).unwrap();
+
+        assert!(last - 200 <= reach, "sigma {sigma} × {scalar} reaches {} but the overlap is {reach}", last - 200);
+    }
+}
+
+#[test]
+fn bands_with_the_overlap_match_the_whole_image() {
+    let (width, height) = (40, 90);
+    let pixels = checkerboard(width, height);
+
+    for (resolution, sample) in [(EdgeResolution::Full, (1, 1)), (EdgeResolution::Full, (2, 3)), (EdgeResolution::Cell, (2, 3))] {
+        let settings = (3.0, 3.0, resolution);
+        let whole = difference_of_gaussians(&pixels, (width, height), settings, sample, (Blending::Srgb, Luma::default()));
+
+        let overlap = overlap(settings, sample.1);
+
+        for (band_top, band_bottom) in [(0, 6), (30, 36), (42, 60), (84, 90)] {
+            let (top, bottom) = (band_top - overlap.min(band_top), (band_bottom + overlap).min(height));
+            let rows = &pixels[(top * width * 4) as usize..(bottom * width * 4) as usize];
+
+            let band = difference_of_gaussians(rows, (width, bottom - top), settings, sample, (Blending::Srgb, Luma::default()));
+
+            let band = &band[((band_top - top) * width) as usize..((band_bottom - top) * width) as usize];
+            let expected = &whole[(band_top * width) as usize..(band_bottom * width) as usize];
+
+            assert_eq!(band, expected, "{resolution:?} with {sample:?} samples, rows {band_top} to {band_bottom}");
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+use apixels::colors::Rgb24;
+use apixels::export::{ans, cast, html, json, rasterise, svg, xbin, Format};
+use apixels::grid::Grid;
+use apixels::sauce::{self, Sauce};
+use apixels::theme::BuiltinTheme;
+use apixels::{Arguments, ColourDepth};
+use clap::{Parser, ValueEnum};
+
+const DEFAULTS: (Rgb24, Rgb24) = (Rgb24 { r: 200, g: 200, b: 200 }, Rgb24 { r: 0, g: 0, b: 0 });
+
+fn parse(text: &str) -> Grid {
+    Grid::parse(text, &BuiltinTheme::Ubuntu.theme(), None)
+}
+
+#[test]
+fn html_escapes_and_groups_colours() {
+    let mut output = Vec::new();
+    html(&parse("\x1b[38;2;255;0;0m<&\x1b[0m>"), DEFAULTS, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.contains("<span style=\"color: #ff0000; background-color: #000000\">&lt;&amp;</span>"));
+    assert!(output.contains("<span style=\"color: #c8c8c8; background-color: #000000\">&gt;</span>"));
+}
+
+#[test]
+fn svg_only_draws_backgrounds_that_differ() {
+    let mut output = Vec::new();
+    svg(&parse("a\x1b[48;2;0;0;255mb c"), DEFAULTS, (8, 16), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(output.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"32\" height=\"16\""));
+    assert_eq!(output.matches("fill=\"#0000ff\"").count(), 1);
+    assert!(output.contains("<rect x=\"8\" y=\"0\" width=\"24\" height=\"16\" fill=\"#0000ff\"/>"));
+    assert!(output.contains(">b c</text>"));
+}
+
+#[test]
+fn quadrants_are_drawn_as_blocks() {
+    let img = rasterise(&parse("\x1b[38;2;255;255;255m▘ "), DEFAULTS, (4, 4));
+
+    assert_eq!((img.width(), img.height()), (8, 4));
+    assert_eq!(img.get_pixel(0, 0).0, [255, 255, 255, 255]);
+    assert_eq!(img.get_pixel(3, 3).0, [0, 0, 0, 255]);
+    assert_eq!(img.get_pixel(6, 1).0, [0, 0, 0, 255]);
+}
+
+#[test]
+fn shades_mix_the_colours() {
+    let img = rasterise(&parse("\x1b[38;2;200;200;200m▒"), DEFAULTS, (2, 2));
+
+    assert_eq!(img.get_pixel(1, 1).0, [100, 100, 100, 255]);
+}
+
+fn sauce() -> Sauce {
+    Sauce { date: "20240101".to_string(), ..Sauce::default() }
+}
+
+#[test]
+fn ans_files_are_cp437_in_vga_colours() {
+    let mut output = Vec::new();
+    ans(&parse("\x1b[38;2;255;80;80;48;2;0;0;170m░▒\x1b[0m█\n⣿▘"), DEFAULTS, false, &sauce(), &mut output).unwrap();
+
+    let art = &output[..output.len() - 129];
+    assert_eq!(art, b"\x1b[0;1;31;44m\xb0\xb1\x1b[0;37;40m\xdb\r\n\xdb\xb0 \r\n\x1b[0m");
+    assert_eq!(sauce::width(&output), Some(80));
+}
+
+#[test]
+fn ice_colours_allow_bright_backgrounds() {
+    let text = "\x1b[48;2;255;85;85mx";
+
+    let mut blinking = Vec::new();
+    ans(&parse(text), DEFAULTS, false, &sauce(), &mut blinking).unwrap();
+    // Which of the dark colours it ends up as doesn't matter, so long as it doesn't blink
+    assert!(blinking.starts_with(b"\x1b[0;37;4"));
+
+    let mut ice = Vec::new();
+    ans(&parse(text), DEFAULTS, true, &sauce(), &mut ice).unwrap();
+    assert!(ice.starts_with(b"\x1b[0;5;37;41mx"));
+}
+
+#[test]
+fn xbin_files_bring_their_own_palette() {
+    let mut output = Vec::new();
+    xbin(&parse("\x1b[38;2;10;20;30;48;2;200;100;0mab"), DEFAULTS, true, &sauce(), &mut output).unwrap();
+
+    assert!(output.starts_with(b"XBIN\x1a\x02\x00\x01\x00\x10\x09"));
+
+    let palette = &output[11..59];
+    let data = &output[59..output.len() - 129];
+
+    let foreground = data[1] & 0xf;
+    let background = data[1] >> 4;
+
+    assert_eq!(&palette[foreground as usize * 3..][..3], &[2, 5, 7]);
+    assert_eq!(&palette[background as usize * 3..][..3], &[50, 25, 0]);
+    assert_eq!(data, &[b'a', data[1], b'b', data[1]]);
+
+    // SAUCE has the size too
+    let record = &output[output.len() - 128..];
+    assert_eq!(&record[94..100], &[6, 0, 2, 0, 1, 0]);
+}
+
+#[test]
+fn art_taller_than_sauce_can_say() {
+    let cell = parse("x").cells[0];
+    let grid = Grid { width: 1, height: 70000, cells: vec![cell; 70000] };
+
+    let mut output = Vec::new();
+    ans(&grid, DEFAULTS, false, &sauce(), &mut output).unwrap();
+    assert_eq!(&output[output.len() - 128..][98..100], &u16::MAX.to_le_bytes());
+
+    assert!(xbin(&grid, DEFAULTS, false, &sauce(), &mut Vec::new()).is_err());
+}
+
+#[test]
+fn formats_go_by_extension() {
+    assert_eq!(Format::from_extension(Path::new("out/ladybug.HTML")), Some(Format::Html));
+    assert_eq!(Format::from_extension(Path::new("ladybug.txt")), Some(Format::Ansi));
+    assert_eq!(Format::from_extension(Path::new("ladybug.cast")), Some(Format::Cast));
+    assert_eq!(Format::from_extension(Path::new("ladybug")), None);
+    assert_eq!(Format::from_extension(Path::new("ladybug.gif")), None);
+
+    for format in Format::value_variants().iter().filter(|format| **format != Format::Irc && **format != Format::Discord && **format != Format::Bbcode) {
+        assert_eq!(Format::from_extension(Path::new(&format!("ladybug.{}", format.extension()))), Some(*format));
+    }
+}
+
+#[test]
+fn output_files_choose_the_format() {
+    let args = |arguments: &[&str]| Arguments::try_parse_from(["apixels", "-f", "ladybug.jpg"].iter().chain(arguments)).unwrap();
+
+    assert_eq!(args(&[]).format(), Format::Ansi);
+    assert_eq!(args(&["-o", "ladybug.svg"]).format(), Format::Svg);
+    assert_eq!(args(&["-o", "ladybug.svg", "--format", "png"]).format(), Format::Png);
+    assert_eq!(args(&["-o", "-"]).output_file(), None);
+    // Text files don't get colours unless they're asked for, like pipes
+    if std::env::var_os("FORCE_COLOR").is_none() {
+        assert_eq!(args(&["-o", "ladybug.txt"]).depth(), ColourDepth::None);
+    }
+    assert_eq!(args(&["-o", "ladybug.txt", "--depth", "ansi"]).depth(), ColourDepth::Ansi);
+    assert_eq!(args(&["-o", "ladybug.html"]).depth(), ColourDepth::Rgb24);
+}
+
+#[test]
+fn json_has_every_cell() {
+    let mut output = Vec::new();
+    json(&parse("\x1b[38;2;255;0;0ma\x1b[0m\"\nb"), &mut output).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+    assert_eq!((value["width"].as_u64(), value["height"].as_u64()), (Some(2), Some(2)));
+    assert_eq!(value["rows"][0][0], serde_json::json!({ "character": "a", "foreground": "#ff0000", "background": null }));
+    assert_eq!(value["rows"][0][1]["character"], "\"");
+    assert_eq!(value["rows"][1][1]["character"], " ");
+}
+
+#[test]
+fn casts_are_one_frame() {
+    let text = "\x1b[31ma\x1b[0m\x00b\nc\n\x1aSAUCE00";
+    let mut output = Vec::new();
+    cast(text, &parse(text), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let lines: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], serde_json::json!({ "version": 2, "width": 2, "height": 2 }));
+    assert_eq!(lines[1], serde_json::json!([0.0, "o", "\x1b[31ma\x1b[0mb\r\nc\r\n"]));
+}
@@ -0,0 +1,184 @@
+use std::path::Path;
+use apixels::colors::Rgb24;
+use apixels::dominant::AlphaMode;
+use apixels::edges::EdgeResolution;
+use apixels::linear::Blending;
+use apixels::luma::Luma;
+use apixels::picker::Picker;
+use apixels::preprocess::Pipeline;
+use apixels::{into_ascii_controlled, into_graphics, ColourDepth, Graphics, Ramp};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use clap::ValueEnum;
+use image::RgbaImage;
+
+// Run with APIXELS_BLESS=1 to write the current output out as the new expected output, after
+// checking that the change in output was meant to happen.
+const BLESS: &str = "APIXELS_BLESS";
+
+/// Renders one of the bundled images with the default settings (apart from the sample size), within
+/// `memory_limit` bytes.
+fn render(image: &str, (sample_width, sample_height): (u32, u32), (depth, grey_levels): (ColourDepth, u16), memory_limit: u64) -> String {
+    let bytes = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join(image)).unwrap();
+
+    let mut output = Vec::new();
+
+    into_ascii_controlled(
+        bytes,
+        (3.0, 3.0, EdgeResolution::Full),
+        (sample_width, sample_height),
+        (false, None),
+        false,
+        Ramp::Ascii,
+        (depth, grey_levels, None),
+        (AlphaMode::Empty, Rgb24::from((0, 0, 0)), 0.5),
+        Picker::ModeMean.picker(),
+        (Blending::Srgb, Luma::default()),
+        &Pipeline::new(),
+        None,
+        memory_limit,
+        &mut output,
+    )
+    .unwrap();
+
+    String::from_utf8(output).unwrap()
+}
+
+fn compare(actual: &str, file_name: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(file_name);
+
+    if std::env::var_os(BLESS).is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("{} is missing, run the tests with {BLESS}=1 to create it", path.display()));
+
+    // Comparing line by line gives a much more useful message than comparing the whole thing
+    for (row, (actual, expected)) in actual.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(actual, expected, "row {row} of {} doesn't match", path.display());
+    }
+
+    assert_eq!(actual.lines().count(), expected.lines().count(), "{} has a different number of rows", path.display());
+}
+
+fn check(image: &str, name: &str, sample: (u32, u32)) {
+    for depth in ColourDepth::value_variants() {
+        let depth_name = depth.to_possible_value().unwrap();
+
+        compare(&render(image, sample, (*depth, 256), 1 << 30), &format!("{name}-{}.txt", depth_name.get_name()));
+    }
+}
+
+#[test]
+fn small() {
+    check("Small.png", "small", (2, 3));
+}
+
+#[test]
+fn small_with_four_greys() {
+    compare(&render("Small.png", (2, 3), (ColourDepth::Grayscale, 4), 1 << 30), "small-grayscale-4.txt");
+}
+
+#[test]
+fn ladybug() {
+    check("Ladybug-header.jpg", "ladybug", (10, 20));
+}
+
+#[test]
+fn bands_join_up_seamlessly() {
+    for (image, sample) in [("Small.png", (2, 3)), ("Ladybug-header.jpg", (10, 20))] {
+        let (width, height) = image::image_dimensions(Path::new(env!("CARGO_MANIFEST_DIR")).join(image)).unwrap();
+        let whole = render(image, sample, (ColourDepth::Rgb24, 256), 1 << 30);
+
+        // Just enough memory for the image and no band at all (which still gets one row of characters
+        // at a time), then bands of a few rows, which have to borrow rows from each other for the blurs
+        for rows in [0, 1, 7, 40, 100] {
+            let memory_limit = (width * height * 4) as u64 + 96 * width as u64 * rows;
+
+            assert_eq!(render(image, sample, (ColourDepth::Rgb24, 256), memory_limit), whole, "{image} in bands of {rows} rows");
+        }
+    }
+}
+
+/// Renders Small.png as graphics, with cells 4×6 pixels big.
+fn graphics(graphics: Graphics) -> String {
+    let bytes = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("Small.png")).unwrap();
+
+    let mut output = Vec::new();
+
+    into_graphics(
+        bytes,
+        graphics,
+        (2, 3),
+        (4, 6),
+        (AlphaMode::Empty, Rgb24::from((0, 0, 0))),
+        16,
+        &Pipeline::new(),
+        1 << 30,
+        &mut output,
+    )
+    .unwrap();
+
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn small_sixel() {
+    compare(&graphics(Graphics::Sixel), "small.sixel");
+}
+
+/// Takes the PNG back out of Kitty or iTerm2 output, checking that the escape codes around it say
+/// it's 15×10 characters big. The PNG itself isn't compared byte for byte, since that depends on
+/// which version of the encoder made it.
+fn inline_png(output: &str) -> RgbaImage {
+    let png = if let Some(rest) = output.strip_prefix("\x1b]1337;File=") {
+        let (header, data) = rest.strip_suffix('\x07').unwrap().split_once(':').unwrap();
+        let png = STANDARD.decode(data).unwrap();
+
+        assert_eq!(header, format!("inline=1;size={};width=15;height=10;preserveAspectRatio=0", png.len()));
+
+        png
+    } else {
+        let chunks: Vec<&str> = output.split_terminator("\x1b\\").collect();
+
+        assert!(chunks[0].starts_with("\x1b_Ga=T,f=100,q=2,c=15,r=10,m="), "{}", chunks[0]);
+
+        STANDARD.decode(chunks.iter().map(|chunk| chunk.split_once(';').unwrap().1).collect::<String>()).unwrap()
+    };
+
+    image::load_from_memory(&png).unwrap().into_rgba8()
+}
+
+fn compare_pixels(actual: &RgbaImage, file_name: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(file_name);
+
+    if std::env::var_os(BLESS).is_some() {
+        actual.save(&path).unwrap();
+
+        return;
+    }
+
+    let expected = image::open(&path)
+        .unwrap_or_else(|_| panic!("{} is missing, run the tests with {BLESS}=1 to create it", path.display()))
+        .into_rgba8();
+
+    assert_eq!(actual.dimensions(), expected.dimensions(), "{} is a different size", path.display());
+
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        assert_eq!(pixel, expected.get_pixel(x, y), "pixel {x},{y} of {} doesn't match", path.display());
+    }
+}
+
+#[test]
+fn small_kitty() {
+    compare_pixels(&inline_png(&graphics(Graphics::Kitty)), "small-inline.png");
+}
+
+#[test]
+fn small_iterm2() {
+    compare_pixels(&inline_png(&graphics(Graphics::Iterm2)), "small-inline.png");
+}
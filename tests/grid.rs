@@ -0,0 +1,125 @@
+use std::path::Path;
+use apixels::colors::Rgb24;
+use apixels::cp437;
+use apixels::grid::Grid;
+use apixels::theme::BuiltinTheme;
+
+fn parse(text: &str) -> Grid {
+    Grid::parse(text, &BuiltinTheme::Ubuntu.theme(), None)
+}
+
+#[test]
+fn plain_text_keeps_its_shape() {
+    let grid = parse("ab\ncde\n");
+
+    assert_eq!((grid.width, grid.height), (3, 2));
+    assert_eq!(grid.row(0).iter().map(|cell| cell.character).collect::<String>(), "ab ");
+    assert_eq!(grid.row(1)[2].character, 'e');
+}
+
+#[test]
+fn sixteen_colours_come_from_the_theme() {
+    let theme = BuiltinTheme::Dracula.theme();
+    let grid = Grid::parse("\x1b[31;44mx\x1b[1;31my\x1b[0mz", &theme, None);
+
+    assert_eq!(grid.cells[0].foreground, Some(theme.ansi[1]));
+    assert_eq!(grid.cells[0].background, Some(theme.ansi[4]));
+    // Bold makes it bright
+    assert_eq!(grid.cells[1].foreground, Some(theme.ansi[9]));
+    assert_eq!(grid.cells[2].foreground, None);
+    assert_eq!(grid.cells[2].background, None);
+}
+
+#[test]
+fn extended_colours() {
+    let grid = parse("\x1b[38;5;196;48;2;1;2;3mx\x1b[39my");
+
+    assert_eq!(grid.cells[0].foreground, Some(Rgb24::from((255, 0, 0))));
+    assert_eq!(grid.cells[0].background, Some(Rgb24::from((1, 2, 3))));
+    assert_eq!(grid.cells[1].foreground, None);
+    assert_eq!(grid.cells[1].background, Some(Rgb24::from((1, 2, 3))));
+}
+
+#[test]
+fn reverse_swaps_the_colours() {
+    let theme = BuiltinTheme::Ubuntu.theme();
+    let grid = Grid::parse("\x1b[7;31mx", &theme, None);
+
+    assert_eq!(grid.cells[0].foreground, theme.background);
+    assert_eq!(grid.cells[0].background, Some(theme.ansi[1]));
+}
+
+#[test]
+fn the_cursor_moves() {
+    let grid = parse("a\x1b[3Cb\x1b[2;2Hc\x1b[sd\x1b[ue");
+
+    assert_eq!(grid.row(0).iter().map(|cell| cell.character).collect::<String>(), "a   b");
+    assert_eq!(grid.row(1).iter().map(|cell| cell.character).collect::<String>(), " ce  ");
+}
+
+#[test]
+fn lines_wrap_when_asked_to() {
+    let grid = Grid::parse("abcdefg", &BuiltinTheme::Ubuntu.theme(), Some(3));
+
+    assert_eq!((grid.width, grid.height), (3, 3));
+    assert_eq!(grid.row(2)[0].character, 'g');
+}
+
+#[test]
+fn the_cursor_stops_at_the_right_margin() {
+    let grid = Grid::parse("a\x1b[20Cbc\x1b[2;99Hd\x1b[3;1H\te", &BuiltinTheme::Ubuntu.theme(), Some(4));
+    let rows: Vec<String> = (0..grid.height).map(|y| grid.row(y).iter().map(|cell| cell.character).collect()).collect();
+
+    // Moving right stops at the last column, and the character after the one drawn there wraps
+    assert_eq!(rows, ["a  b", "c  d", "   e"]);
+}
+
+#[test]
+fn far_away_cursors_dont_make_huge_grids() {
+    for text in ["\x1b[99999999;99999999Hx", "\x1b[99999999Bx\x1b[99999999Cy", "\x1b[4294967295;4294967295fx"] {
+        let grid = parse(text);
+
+        assert!(grid.width <= 4096 && grid.height <= 16384, "{text:?} is {}×{}", grid.width, grid.height);
+        assert!(grid.cells.iter().any(|cell| cell.character == 'x'), "{text:?}");
+    }
+
+    let grid = Grid::parse("\x1b[99999999;99999999Hx", &BuiltinTheme::Ubuntu.theme(), Some(80));
+
+    assert_eq!(grid.width, 80);
+    assert_eq!(grid.row(grid.height - 1)[79].character, 'x');
+}
+
+#[test]
+fn sauce_and_other_escape_codes_are_left_out() {
+    let grid = parse("\x1b]0;title\x07\x1b[?25la\x1bPq#0!9~\x1b\\b\x1aSAUCE00");
+
+    assert_eq!((grid.width, grid.height), (2, 1));
+    assert_eq!(grid.row(0)[1].character, 'b');
+}
+
+#[test]
+fn cp437_decodes_the_block_characters() {
+    assert_eq!(cp437::decode(b"\x1b[0m\xdb\xb0\xb1\xb2 \xc9\xcd\xbb\x7f"), "\x1b[0m█░▒▓ ╔═╗⌂");
+}
+
+#[test]
+fn cp437_encodes_what_it_decodes() {
+    let bytes: Vec<u8> = (0..=255).collect();
+
+    for (byte, character) in cp437::decode(&bytes).chars().enumerate() {
+        assert_eq!(cp437::encode(character), Some(byte as u8));
+    }
+
+    assert_eq!(cp437::encode('⣿'), None);
+}
+
+#[test]
+fn apixels_output_reads_back_in() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/small-rgb24.txt");
+    let grid = parse(&std::fs::read_to_string(path).unwrap());
+
+    // Small.png is 30×30, in 2×3 samples
+    assert_eq!((grid.width, grid.height), (15, 10));
+    assert_eq!(grid.cells[0].character, '-');
+    assert_eq!(grid.cells[0].foreground, Some(Rgb24::from((255, 255, 255))));
+}
@@ -0,0 +1,96 @@
+use apixels::colors::Rgb24;
+use apixels::dominant::AlphaMode;
+use apixels::inline::{iterm2, kitty, png};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{Rgba, RgbaImage};
+
+/// Noise doesn't compress, so the PNG is big enough to need more than one chunk. It comes from a
+/// xorshift generator, since anything that's just arithmetic on x and y has patterns that deflate
+/// can find.
+fn noise() -> RgbaImage {
+    let mut state = 0x2545f4914f6cdd1du64;
+
+    RgbaImage::from_fn(64, 64, |_, _| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+
+        Rgba([state as u8, (state >> 8) as u8, (state >> 16) as u8, 255])
+    })
+}
+
+#[test]
+fn kitty_splits_images_into_chunks() {
+    let png = png(&noise(), (AlphaMode::Empty, Rgb24::from((0, 0, 0)))).unwrap();
+
+    let mut output = Vec::new();
+    kitty(&png, (8, 4), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    let chunks: Vec<&str> = output.split_terminator("\x1b\\").collect();
+
+    // 64×64 pixels of noise is 12KiB of RGB, which is 16KiB of base64
+    assert!(png.len() > 12 * 1024, "{}", png.len());
+    assert!(chunks.len() > 3, "{}", chunks.len());
+    assert!(chunks[0].starts_with("\x1b_Ga=T,f=100,q=2,c=8,r=4,m=1;"), "{}", &chunks[0][..40]);
+    assert!(chunks[1..chunks.len() - 1].iter().all(|chunk| chunk.starts_with("\x1b_Gm=1;")));
+    assert!(chunks[chunks.len() - 1].starts_with("\x1b_Gm=0;"));
+
+    assert!(chunks.iter().all(|chunk| chunk.split_once(';').unwrap().1.len() <= 4096));
+}
+
+#[test]
+fn kitty_chunks_are_full_until_the_last_one() {
+    // 6000 bytes is 8000 bytes of base64: one full chunk, and 3904 bytes in the next
+    let payload: Vec<u8> = (0..6000).map(|index| index as u8).collect();
+
+    let mut output = Vec::new();
+    kitty(&payload, (1, 1), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    let chunks: Vec<(&str, &str)> = output.split_terminator("\x1b\\").map(|chunk| chunk.split_once(';').unwrap()).collect();
+
+    assert_eq!(chunks.iter().map(|(header, data)| (*header, data.len())).collect::<Vec<_>>(), [
+        ("\x1b_Ga=T,f=100,q=2,c=1,r=1,m=1", 4096),
+        ("\x1b_Gm=0", 3904),
+    ]);
+    assert_eq!(STANDARD.decode(chunks.iter().map(|(_, data)| *data).collect::<String>()).unwrap(), payload);
+}
+
+#[test]
+fn small_images_fit_in_one_chunk() {
+    let png = png(&RgbaImage::new(4, 4), (AlphaMode::Empty, Rgb24::from((0, 0, 0)))).unwrap();
+
+    let mut output = Vec::new();
+    kitty(&png, (1, 1), &mut output).unwrap();
+
+    assert!(String::from_utf8(output).unwrap().starts_with("\x1b_Ga=T,f=100,q=2,c=1,r=1,m=0;"));
+}
+
+#[test]
+fn iterm2_says_how_big_the_file_is() {
+    let png = png(&noise(), (AlphaMode::Empty, Rgb24::from((0, 0, 0)))).unwrap();
+
+    let mut output = Vec::new();
+    iterm2(&png, (10, 5), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    let header = format!("\x1b]1337;File=inline=1;size={};width=10;height=5;preserveAspectRatio=0:", png.len());
+
+    assert!(output.starts_with(&header));
+    assert!(output.ends_with('\x07'));
+}
+
+#[test]
+fn compositing_gets_rid_of_transparency() {
+    let img = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 0]));
+
+    let decode = |png: Vec<u8>| image::load_from_memory(&png).unwrap().into_rgba8();
+
+    let kept = decode(png(&img, (AlphaMode::Empty, Rgb24::from((255, 0, 0)))).unwrap());
+    let composited = decode(png(&img, (AlphaMode::Composite, Rgb24::from((255, 0, 0)))).unwrap());
+
+    assert_eq!(kept.get_pixel(0, 0)[3], 0);
+    assert_eq!(composited.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+}
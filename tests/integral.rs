@@ -0,0 +1,102 @@
+use std::path::Path;
+use apixels::colors::Rgb24;
+use apixels::dominant::AlphaMode;
+use apixels::edges::EdgeResolution;
+use apixels::integral::IntegralImage;
+use apixels::linear::Blending;
+use apixels::luma::Luma;
+use apixels::picker::Picker;
+use apixels::preprocess::Pipeline;
+use apixels::{into_ascii_controlled, ColourDepth, Ramp};
+use proptest::prelude::*;
+
+/// Adds up a rectangle the slow way, cut off at the edges of the image like `sum` does.
+fn brute_force(values: &[u64], image_width: u32, (x, y, width, height): (u32, u32, u32, u32)) -> u128 {
+    let image_height = values.len() as u32 / image_width;
+
+    (y..(y + height).min(image_height))
+        .flat_map(|row| (x..(x + width).min(image_width)).map(move |column| (row, column)))
+        .map(|(row, column)| values[(row * image_width + column) as usize] as u128)
+        .sum()
+}
+
+fn image(values: &[u64], width: u32) -> IntegralImage<1> {
+    IntegralImage::from_fn(width, values.len() as u32 / width, |x, y| [values[(y * width + x) as usize]])
+}
+
+/// An image and some rectangle in (or partly outside of) it.
+fn image_and_rectangle(max_value: u64) -> impl Strategy<Value = (Vec<u64>, u32, (u32, u32, u32, u32))> {
+    (1u32..48, 1u32..48).prop_flat_map(move |(width, height)| {
+        (
+            prop::collection::vec(0..=max_value, (width * height) as usize),
+            Just(width),
+            (0..width + 4, 0..height + 4, 0..width + 4, 0..height + 4),
+        )
+    })
+}
+
+#[test]
+fn sums_survive_the_table_wrapping_around() {
+    // The whole table adds up to 2^68, but a 15×15 rectangle is still under 2^64
+    let values = vec![1 << 56; 64 * 64];
+    let image = image(&values, 64);
+
+    assert_eq!(image.sum(40, 40, 15, 15), [225 << 56]);
+    assert_eq!(image.sum(0, 0, 1, 1), [1 << 56]);
+    assert_eq!(image.sum(63, 63, 10, 10), [1 << 56]);
+}
+
+#[test]
+fn every_channel_is_summed_separately() {
+    let image = IntegralImage::from_fn(3, 2, |x, y| [x as u64, y as u64, 1]);
+
+    assert_eq!((image.width(), image.height()), (3, 2));
+    assert_eq!(image.sum(0, 0, 3, 2), [6, 3, 6]);
+    assert_eq!(image.sum(1, 1, 2, 1), [3, 2, 2]);
+    assert_eq!(image.sum(3, 0, 1, 1), [0, 0, 0]);
+}
+
+#[test]
+fn samples_can_be_as_big_as_you_like() {
+    // A sample bigger than the image is just one cell, the size of the image
+    let bytes = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("Small.png")).unwrap();
+
+    let mut output = Vec::new();
+
+    into_ascii_controlled(
+        bytes,
+        (3.0, 3.0, EdgeResolution::Full),
+        (1000, 1000),
+        (false, None),
+        false,
+        Ramp::Ascii,
+        (ColourDepth::None, 256, None),
+        (AlphaMode::Empty, Rgb24::from((0, 0, 0)), 0.5),
+        Picker::ModeMean.picker(),
+        (Blending::Linear, Luma::default()),
+        &Pipeline::new(),
+        None,
+        1 << 30,
+        &mut output,
+    )
+    .unwrap();
+
+    assert_eq!(String::from_utf8(output).unwrap().lines().count(), 1);
+}
+
+proptest! {
+    #[test]
+    fn sums_match_adding_up_the_pixels((values, width, rectangle) in image_and_rectangle(255 * 255)) {
+        let (x, y, rectangle_width, rectangle_height) = rectangle;
+
+        prop_assert_eq!(image(&values, width).sum(x, y, rectangle_width, rectangle_height)[0] as u128, brute_force(&values, width, rectangle));
+    }
+
+    #[test]
+    fn sums_are_right_modulo_two_to_the_64((values, width, rectangle) in image_and_rectangle(u64::MAX)) {
+        let (x, y, rectangle_width, rectangle_height) = rectangle;
+
+        // Big enough values wrap the table (and maybe the rectangle) around, but what's left over is still right
+        prop_assert_eq!(image(&values, width).sum(x, y, rectangle_width, rectangle_height)[0], brute_force(&values, width, rectangle) as u64);
+    }
+}
@@ -0,0 +1,66 @@
+use apixels::linear::{linear_to_srgb, srgb_to_linear, Blending};
+
+// Reference values from the sRGB transfer function (IEC 61966-2-1)
+const REFERENCE: [(u8, f32); 8] = [
+    (0, 0.0),
+    (1, 0.000303527),
+    (10, 0.00303527),
+    (11, 0.003346536),
+    (64, 0.051269458),
+    (128, 0.2158605),
+    (188, 0.5028865),
+    (255, 1.0),
+];
+
+#[test]
+fn decodes_reference_values() {
+    for (srgb, linear) in REFERENCE {
+        let decoded = srgb_to_linear(srgb);
+
+        assert!((decoded - linear).abs() < 1e-6, "{srgb} decoded to {decoded}, expected {linear}");
+    }
+}
+
+#[test]
+fn encodes_reference_values() {
+    for (srgb, linear) in REFERENCE {
+        assert_eq!(linear_to_srgb(linear), srgb, "{linear} should encode to {srgb}");
+    }
+
+    assert_eq!(linear_to_srgb(0.5), 188);
+    assert_eq!(linear_to_srgb(-1.0), 0);
+    assert_eq!(linear_to_srgb(2.0), 255);
+}
+
+#[test]
+fn round_trips_every_value() {
+    for srgb in 0..=255 {
+        assert_eq!(linear_to_srgb(srgb_to_linear(srgb)), srgb);
+    }
+}
+
+#[test]
+fn linear_mean_is_brighter() {
+    let black_and_white = [[0, 0, 0], [255, 255, 255]];
+
+    assert_eq!(Blending::Srgb.mean(&black_and_white), [128, 128, 128]);
+    assert_eq!(Blending::Linear.mean(&black_and_white), [188, 188, 188]);
+
+    // Pure red and pure green mixed in linear light are a bright yellow, not a muddy olive
+    let red_and_green = [[255, 0, 0], [0, 255, 0]];
+
+    assert_eq!(Blending::Srgb.mean(&red_and_green), [128, 128, 0]);
+    assert_eq!(Blending::Linear.mean(&red_and_green), [188, 188, 0]);
+    assert_eq!(Blending::Linear.mix([255, 0, 0], [0, 255, 0]), [188, 188, 0]);
+}
+
+#[test]
+fn linear_mean_of_a_flat_sample_is_unchanged() {
+    for value in [0, 1, 17, 128, 200, 255] {
+        let flat = [[value, value, value]; 6];
+
+        assert_eq!(Blending::Linear.mean(&flat), [value; 3]);
+        assert_eq!(Blending::Linear.rms(&flat), [value; 3]);
+        assert_eq!(Blending::Linear.mean_luma([value; 6].into_iter()), value);
+    }
+}
@@ -0,0 +1,61 @@
+use apixels::luma::Luma;
+use clap::ValueEnum;
+use proptest::prelude::*;
+
+#[test]
+fn black_and_white_stay_put() {
+    for luma in Luma::value_variants() {
+        assert_eq!(luma.luma((0, 0, 0)), 0, "{luma:?}");
+        assert_eq!(luma.luma((255, 255, 255)), 255, "{luma:?}");
+    }
+}
+
+#[test]
+fn weights_match_the_standards() {
+    // The weight of each channel, times 255 and rounded
+    assert_eq!([(255, 0, 0), (0, 255, 0), (0, 0, 255)].map(|colour| Luma::Rec601.luma(colour)), [76, 150, 29]);
+    assert_eq!([(255, 0, 0), (0, 255, 0), (0, 0, 255)].map(|colour| Luma::Rec709.luma(colour)), [54, 182, 18]);
+    assert_eq!([(255, 0, 0), (0, 255, 0), (0, 0, 255)].map(|colour| Luma::Rec2100.luma(colour)), [67, 173, 15]);
+    assert_eq!([(255, 0, 0), (0, 255, 0), (0, 0, 255)].map(|colour| Luma::Average.luma(colour)), [85, 85, 85]);
+    assert_eq!([(255, 0, 0), (0, 255, 0), (0, 0, 255)].map(|colour| Luma::HslLightness.luma(colour)), [128, 128, 128]);
+}
+
+#[test]
+fn cie_lightness_puts_middle_grey_in_the_middle() {
+    // sRGB 119 is 18.4% luminance, which is about as close to L* = 50 as it gets
+    assert_eq!(Luma::CieLightness.luma((119, 119, 119)), 128);
+}
+
+#[test]
+fn luminance_of_pure_colours() {
+    // 21.26% of white's luminance is sRGB 127, and so on
+    assert_eq!([(255, 0, 0), (0, 255, 0), (0, 0, 255)].map(|colour| Luma::Luminance.luma(colour)), [127, 220, 76]);
+}
+
+proptest! {
+    #[test]
+    fn greys_are_left_alone(grey in any::<u8>()) {
+        // L* is spaced differently to sRGB, so it's the only one that moves greys around
+        for luma in Luma::value_variants().iter().filter(|luma| **luma != Luma::CieLightness) {
+            let value = luma.luma((grey, grey, grey));
+
+            prop_assert!(value.abs_diff(grey) <= 1, "{:?} turned {} into {}", luma, grey, value);
+        }
+    }
+
+    #[test]
+    fn green_is_brighter_than_blue(value in 1u8..) {
+        for luma in [Luma::Rec601, Luma::Rec709, Luma::Rec2100, Luma::Luminance, Luma::CieLightness] {
+            prop_assert!(luma.luma((0, value, 0)) >= luma.luma((0, 0, value)), "{:?}", luma);
+        }
+    }
+
+    #[test]
+    fn weighted_lumas_sit_between_the_channels(r in any::<u8>(), g in any::<u8>(), b in any::<u8>()) {
+        let (min, max) = (r.min(g).min(b), r.max(g).max(b));
+
+        for luma in [Luma::Average, Luma::Rec601, Luma::Rec709, Luma::Rec2100, Luma::HslLightness] {
+            prop_assert!((min..=max).contains(&luma.luma((r, g, b))), "{:?}", luma);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use apixels::{band_height, Arguments};
+use clap::Parser;
+
+#[test]
+fn more_memory_never_means_shorter_bands() {
+    for (width, overlap, sample_height) in [(1, 0, 1), (1, 25, 20), (1000, 25, 3), (40_000, 75, 7), (u32::MAX, 10, 2)] {
+        let budgets = (0..64).map(|power| 1u64 << power).chain([u64::MAX]);
+
+        let heights: Vec<u32> = budgets.map(|budget| band_height(budget, width, overlap, sample_height)).collect();
+
+        assert!(heights.windows(2).all(|pair| pair[0] <= pair[1]), "{width} wide: {heights:?}");
+        assert!(heights.iter().all(|height| *height >= sample_height && height % sample_height == 0), "{width} wide: {heights:?}");
+    }
+}
+
+#[test]
+fn bands_stream_even_with_plenty_of_memory() {
+    // 32 rows of characters, unless the overlap would make that wasteful
+    assert_eq!(band_height(u64::MAX, 1000, 25, 20), 640);
+    assert_eq!(band_height(u64::MAX, 1000, 25, 2), 200);
+}
+
+#[test]
+fn tight_budgets_still_get_one_row_of_characters() {
+    assert_eq!(band_height(0, 1000, 25, 20), 20);
+}
+
+#[test]
+fn huge_memory_limits_dont_overflow() {
+    let args = Arguments::try_parse_from(["apixels", "-f", "Small.png", "--memory-limit", &u64::MAX.to_string()]).unwrap();
+
+    assert_eq!(args.memory_limit(), u64::MAX);
+}
@@ -0,0 +1,96 @@
+use apixels::linear::Blending;
+use apixels::picker::{Picker, Sample};
+use clap::ValueEnum;
+use color_thief::Color as CtColor;
+
+const RED: [u8; 3] = [200, 30, 30];
+const BLUE: [u8; 3] = [30, 30, 200];
+
+fn pick(picker: Picker, pixels: &[[u8; 3]], blending: Blending) -> [[u8; 3]; 2] {
+    picker.picker().pick(&Sample::new(pixels), blending).map(|CtColor { r, g, b }| [r, g, b])
+}
+
+/// Three quarters red, one quarter blue.
+fn two_colours() -> Vec<[u8; 3]> {
+    (0..64).map(|idx| if idx % 4 == 3 { BLUE } else { RED }).collect()
+}
+
+#[test]
+fn uniform_samples_give_their_colour() {
+    for colour in [[200, 100, 50], [255, 255, 255], [1, 2, 3], [128, 64, 190]] {
+        let pixels = vec![colour; 48];
+
+        for picker in Picker::value_variants() {
+            for blending in [Blending::Srgb, Blending::Linear] {
+                assert_eq!(pick(*picker, &pixels, blending), [colour; 2], "{picker:?} with {blending:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn single_pixels_give_their_colour() {
+    for picker in Picker::value_variants() {
+        assert_eq!(pick(*picker, &[RED], Blending::Srgb), [RED; 2], "{picker:?}");
+    }
+}
+
+#[test]
+fn two_colour_pickers_find_both() {
+    for picker in [Picker::ModeMean, Picker::KMeans, Picker::Contrast, Picker::Palette] {
+        let [first, second] = pick(picker, &two_colours(), Blending::Srgb);
+
+        assert_ne!(first, second, "{picker:?}");
+    }
+}
+
+#[test]
+fn clusters_and_palettes_are_the_two_colours() {
+    // Most of the sample is red, so that comes first
+    assert_eq!(pick(Picker::KMeans, &two_colours(), Blending::Srgb), [RED, BLUE]);
+
+    // colour_thief doesn't promise which order they come in
+    let mut palette = pick(Picker::Palette, &two_colours(), Blending::Srgb);
+    palette.sort();
+
+    assert_eq!(palette, [BLUE, RED]);
+}
+
+#[test]
+fn contrast_puts_the_colour_nearer_the_mean_first() {
+    assert_eq!(pick(Picker::Contrast, &two_colours(), Blending::Srgb), [RED, BLUE]);
+}
+
+#[test]
+fn mode_mean_leans_towards_the_most_common_colour() {
+    let [dominant, average] = pick(Picker::ModeMean, &two_colours(), Blending::Srgb);
+
+    // The dominant colour is halfway between the red and the average, which is pulled towards blue
+    assert!(dominant[0] > average[0] && dominant[2] < average[2], "{dominant:?}, {average:?}");
+}
+
+#[test]
+fn flat_pickers_use_one_colour_for_both() {
+    // The mean is three quarters of the way to red, and the median is red
+    assert_eq!(pick(Picker::Mean, &two_colours(), Blending::Srgb), [[158, 30, 73]; 2]);
+    assert_eq!(pick(Picker::Median, &two_colours(), Blending::Srgb), [RED; 2]);
+}
+
+#[test]
+fn known_sums_give_the_same_answer() {
+    let pixels = two_colours();
+    let sums = pixels.iter().fold([0u64; 6], |mut sums, pixel| {
+        for channel in 0..3 {
+            sums[channel] += pixel[channel] as u64;
+            sums[channel + 3] += (pixel[channel] as u64).pow(2);
+        }
+
+        sums
+    });
+
+    for picker in Picker::value_variants() {
+        let with_sums = picker.picker().pick(&Sample { pixels: &pixels, sums: Some(sums) }, Blending::Srgb);
+
+        assert_eq!(with_sums.map(|CtColor { r, g, b }| [r, g, b]), pick(*picker, &pixels, Blending::Srgb), "{picker:?}");
+    }
+}
@@ -0,0 +1,90 @@
+use apixels::preprocess::{Adjustment, Pipeline};
+use image::{Rgba, RgbaImage};
+
+fn flat(width: u32, height: u32, grey: u8) -> RgbaImage {
+    RgbaImage::from_pixel(width, height, Rgba([grey, grey, grey, 255]))
+}
+
+/// Goes from black on the left to white on the right.
+fn gradient(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, _| {
+        let grey = (x * 255 / (width - 1)) as u8;
+
+        Rgba([grey, grey, grey, 255])
+    })
+}
+
+fn greys(img: &RgbaImage) -> Vec<u8> {
+    img.pixels().map(|Rgba([r, _, _, _])| *r).collect()
+}
+
+fn apply(adjustment: Adjustment, mut img: RgbaImage) -> RgbaImage {
+    adjustment.apply(&mut img);
+
+    img
+}
+
+#[test]
+fn equalising_a_flat_image_leaves_it_alone() {
+    for grey in [0, 100, 255] {
+        let img = apply(Adjustment::Equalise, flat(10, 10, grey));
+
+        assert!(greys(&img).iter().all(|value| *value == grey), "{grey}");
+    }
+}
+
+#[test]
+fn equalising_spreads_the_values_out() {
+    let img = RgbaImage::from_fn(4, 1, |x, _| Rgba([[100, 101, 102, 103][x as usize]; 4]));
+
+    assert_eq!(greys(&apply(Adjustment::Equalise, img)), [0, 85, 170, 255]);
+}
+
+#[test]
+fn clahe_keeps_flat_images_flat() {
+    for (width, height) in [(90, 60), (100, 100), (7, 3), (1, 1)] {
+        let img = apply(Adjustment::Clahe { tiles: 8, clip_limit: 2.0 }, flat(width, height, 100));
+        let greys = greys(&img);
+
+        assert!(greys.iter().all(|value| *value == greys[0]), "{width}×{height}: {greys:?}");
+        assert!(greys[0].abs_diff(100) <= 2, "{width}×{height}: {}", greys[0]);
+    }
+}
+
+#[test]
+fn clahe_keeps_gradients_in_order() {
+    for (width, height, tiles) in [(20, 20, 8), (90, 60, 8), (100, 10, 4)] {
+        let img = apply(Adjustment::Clahe { tiles, clip_limit: 2.0 }, gradient(width, height));
+
+        for y in 0..height {
+            let row: Vec<u8> = (0..width).map(|x| img.get_pixel(x, y).0[0]).collect();
+
+            assert!(row.windows(2).all(|pair| pair[0] <= pair[1]), "{width}×{height} in {tiles} tiles, row {y}: {row:?}");
+        }
+    }
+}
+
+#[test]
+fn clahe_leaves_alpha_alone() {
+    let img = RgbaImage::from_fn(16, 16, |x, y| Rgba([(x * 16) as u8, (y * 16) as u8, 0, (x + y) as u8]));
+    let adjusted = apply(Adjustment::Clahe { tiles: 4, clip_limit: 2.0 }, img.clone());
+
+    assert!(img.pixels().zip(adjusted.pixels()).all(|(before, after)| before.0[3] == after.0[3]));
+}
+
+#[test]
+fn auto_levels_stretches_each_channel() {
+    let img = RgbaImage::from_fn(2, 1, |x, _| if x == 0 { Rgba([50, 0, 10, 255]) } else { Rgba([150, 255, 20, 255]) });
+    let img = apply(Adjustment::AutoLevels, img);
+
+    assert_eq!(img.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+    assert_eq!(img.get_pixel(1, 0), &Rgba([255, 255, 255, 255]));
+}
+
+#[test]
+fn adjustments_go_in_order() {
+    let pipeline = Pipeline::new().then(Adjustment::Brightness(50)).then(Adjustment::Invert);
+
+    assert_eq!(greys(&pipeline.apply(flat(1, 1, 100))), [105]);
+    assert!(Pipeline::new().is_empty());
+}
@@ -0,0 +1,68 @@
+use std::time::{Duration, SystemTime};
+use apixels::sauce::{self, Kind, Sauce};
+
+fn sauce() -> Sauce {
+    Sauce {
+        title: "Ladybug".to_string(),
+        author: "Ré".to_string(),
+        group: "apixels".to_string(),
+        date: "20240229".to_string(),
+        font: "IBM VGA".to_string(),
+    }
+}
+
+#[test]
+fn records_are_128_bytes_after_the_eof() {
+    let mut output = Vec::new();
+    sauce().write(Kind::Ansi { width: 80, height: 25, ice: true }, 1234, &mut output).unwrap();
+
+    assert_eq!(output.len(), 129);
+    assert_eq!(output[0], 0x1a);
+
+    let record = &output[1..];
+    assert!(record.starts_with(b"SAUCE00Ladybug   "));
+    // The author is in CP437
+    assert_eq!(&record[42..46], b"R\x82  ");
+    assert_eq!(&record[82..90], b"20240229");
+    assert_eq!(&record[90..94], &1234u32.to_le_bytes());
+    assert_eq!(&record[94..100], &[1, 1, 80, 0, 25, 0]);
+    assert_eq!(record[105], 1);
+    assert_eq!(&record[106..115], b"IBM VGA\0\0");
+}
+
+#[test]
+fn long_fields_are_cut_short() {
+    let mut output = Vec::new();
+    let sauce = Sauce { title: "x".repeat(50), ..sauce() };
+    sauce.write(Kind::Xbin { width: 80, height: 25 }, 0, &mut output).unwrap();
+
+    assert_eq!(output.len(), 129);
+    assert_eq!(&output[37..44], b"xxxxxxR");
+    assert_eq!(&output[95..97], &[6, 0]);
+}
+
+#[test]
+fn xbin_records_have_the_size_and_no_font() {
+    let mut output = Vec::new();
+    sauce().write(Kind::Xbin { width: 132, height: 300 }, 0, &mut output).unwrap();
+
+    let record = &output[1..];
+    assert_eq!(&record[94..100], &[6, 0, 132, 0, 44, 1]);
+    assert_eq!(&record[106..128], &[0; 22]);
+}
+
+#[test]
+fn widths_come_back_out() {
+    let mut file = b"art".to_vec();
+    sauce().write(Kind::Ansi { width: 160, height: 50, ice: false }, 3, &mut file).unwrap();
+
+    assert_eq!(sauce::width(&file), Some(160));
+    assert_eq!(sauce::width(b"no record here"), None);
+}
+
+#[test]
+fn dates() {
+    assert_eq!(sauce::date(SystemTime::UNIX_EPOCH), "19700101");
+    assert_eq!(sauce::date(SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_164_800)), "20240229");
+    assert_eq!(sauce::date(SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400)), "20000229");
+}
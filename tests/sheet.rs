@@ -0,0 +1,68 @@
+use apixels::colors::Rgb24;
+use apixels::grid::Grid;
+use apixels::sheet::{compose, Tile};
+use apixels::theme::BuiltinTheme;
+use apixels::transform::Transform;
+use image::RgbaImage;
+
+fn tile(text: &str, caption: &str) -> Tile {
+    Tile {
+        grid: Grid::parse(text, &BuiltinTheme::Ubuntu.theme(), None),
+        caption: caption.to_string(),
+    }
+}
+
+fn text(grid: &Grid) -> Vec<String> {
+    (0..grid.height).map(|y| grid.row(y).iter().map(|cell| cell.character).collect()).collect()
+}
+
+#[test]
+fn tiles_are_centred_in_their_slots() {
+    let tiles = [tile("ab\ncd", "one"), tile("efgh", "two"), tile("i", "three")];
+    let sheet = compose(&tiles, 2, (4, 2), (1, 1), false);
+
+    assert_eq!(text(&sheet), [
+        " ab  efgh",
+        " cd      ",
+        "         ",
+        " i       ",
+        "         ",
+    ]);
+}
+
+#[test]
+fn captions_go_underneath() {
+    let tiles = [tile("ab", "one"), tile("cd", "a-long-name.png")];
+    let sheet = compose(&tiles, 3, (5, 1), (2, 0), true);
+
+    assert_eq!(text(&sheet), [
+        " ab     cd  ",
+        " one   a-lo…",
+    ]);
+}
+
+#[test]
+fn tiles_keep_their_colours_and_get_cut_off() {
+    let tiles = [tile("\x1b[38;2;255;0;0mabcdef\nghijkl\nmnopqr", "red")];
+    let sheet = compose(&tiles, 1, (4, 2), (0, 0), false);
+
+    assert_eq!(text(&sheet), ["abcd", "ghij"]);
+    assert!(sheet.cells.iter().all(|cell| cell.foreground == Some(Rgb24::from((255, 0, 0)))));
+}
+
+#[test]
+fn pasting_clips_to_the_grid() {
+    let mut grid = Grid::blank(3, 2);
+    grid.paste(&tile("abcd\nefgh\nijkl", "").grid, (1, 1));
+
+    assert_eq!(text(&grid), ["   ", " ab"]);
+}
+
+#[test]
+fn fitting_keeps_the_aspect_ratio() {
+    let img = RgbaImage::new(300, 100);
+
+    assert_eq!(Transform::Fit(60, 60).apply(&img).dimensions(), (60, 20));
+    assert_eq!(Transform::Fit(900, 150).apply(&img).dimensions(), (450, 150));
+    assert_eq!(Transform::Fit(2, 2).apply(&img).dimensions(), (2, 1));
+}
@@ -0,0 +1,65 @@
+use apixels::colors::Rgb24;
+use apixels::dominant::AlphaMode;
+use apixels::sixel::{encode, nearest, palette};
+use image::{Rgba, RgbaImage};
+
+fn sixel(img: &RgbaImage, colours: u16, alpha: AlphaMode) -> String {
+    let mut output = Vec::new();
+
+    encode(img, colours, (alpha, Rgb24::from((0, 0, 0))), &mut output).unwrap();
+
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn streams_are_wrapped_in_a_dcs() {
+    let text = sixel(&RgbaImage::from_pixel(3, 4, Rgba([255, 0, 0, 255])), 256, AlphaMode::Empty);
+
+    assert!(text.starts_with("\x1bP0;1;0q\"1;1;3;4#0;2;"), "{text:?}");
+    assert!(text.ends_with("\x1b\\"), "{text:?}");
+}
+
+#[test]
+fn runs_are_compressed() {
+    // 4 rows is the bottom 4 bits of each sixel, so `?` + 15 = `N`
+    let text = sixel(&RgbaImage::from_pixel(10, 4, Rgba([0, 0, 255, 255])), 256, AlphaMode::Empty);
+
+    assert!(text.ends_with("#0!10N\x1b\\"), "{text:?}");
+}
+
+#[test]
+fn bands_are_six_rows_high() {
+    let text = sixel(&RgbaImage::from_pixel(2, 13, Rgba([0, 255, 0, 255])), 256, AlphaMode::Empty);
+
+    assert!(text.ends_with("#0~~-#0~~-#0@@\x1b\\"), "{text:?}");
+}
+
+#[test]
+fn transparent_pixels_are_left_alone() {
+    let mut img = RgbaImage::from_pixel(4, 1, Rgba([0, 0, 255, 255]));
+    img.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+    img.put_pixel(3, 0, Rgba([0, 0, 0, 0]));
+
+    assert!(sixel(&img, 256, AlphaMode::Empty).ends_with("#0?@@\x1b\\"));
+
+    // Unless they're meant to be blended onto the matte, which is black
+    let composited = sixel(&img, 256, AlphaMode::Composite);
+    assert!(composited.contains("@??@"), "{composited:?}");
+}
+
+#[test]
+fn palettes_stay_within_the_limit() {
+    let pixels: Vec<[u8; 3]> = (0..=255).flat_map(|r| (0..=255).step_by(15).map(move |g| [r, g, 255 - r])).collect();
+
+    for colours in [2, 16, 255, 256] {
+        assert!(palette(&pixels, colours).len() <= colours as usize);
+    }
+}
+
+#[test]
+fn white_gets_into_the_palette() {
+    let palette = palette(&[[255, 255, 255], [0, 0, 0], [200, 0, 0]], 16);
+
+    assert!(palette.contains(&[255, 255, 255]), "{palette:?}");
+    assert_eq!(palette[nearest(&palette, [254, 253, 255]) as usize], [255, 255, 255]);
+}
@@ -0,0 +1,150 @@
+use apixels::colors::Rgb24;
+use apixels::terminal::{detect_depth, detect_graphics_with, parse_attributes_reply, parse_background_reply, parse_cell_size_reply, Background, Terminfo};
+use apixels::{ColourDepth, Graphics};
+
+/// Detects the depth with only the given environment variables set, and a terminfo database that
+/// only knows about xterm-256color and a monochrome vt100.
+fn detect(vars: &[(&str, &str)], is_terminal: bool) -> ColourDepth {
+    let var = |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string());
+
+    let terminfo = |term: &str| match term {
+        "xterm-256color" => Some(Terminfo { colours: Some(256), truecolour: false }),
+        "xterm-tc" => Some(Terminfo { colours: Some(256), truecolour: true }),
+        "xterm" => Some(Terminfo { colours: Some(8), truecolour: false }),
+        "vt100" => Some(Terminfo { colours: None, truecolour: false }),
+        _ => None,
+    };
+
+    detect_depth(var, is_terminal, terminfo)
+}
+
+#[test]
+fn colorterm_means_truecolour() {
+    assert_eq!(detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")], true), ColourDepth::Rgb24);
+    assert_eq!(detect(&[("TERM", "xterm"), ("COLORTERM", "24bit")], true), ColourDepth::Rgb24);
+}
+
+#[test]
+fn terminfo_decides_otherwise() {
+    assert_eq!(detect(&[("TERM", "xterm-256color")], true), ColourDepth::Ansi256);
+    assert_eq!(detect(&[("TERM", "xterm-tc")], true), ColourDepth::Rgb24);
+    assert_eq!(detect(&[("TERM", "xterm")], true), ColourDepth::Ansi);
+    assert_eq!(detect(&[("TERM", "vt100")], true), ColourDepth::None);
+}
+
+#[test]
+fn term_names_are_a_fallback() {
+    assert_eq!(detect(&[("TERM", "screen-256color")], true), ColourDepth::Ansi256);
+    assert_eq!(detect(&[("TERM", "xterm-kitty")], true), ColourDepth::Rgb24);
+    assert_eq!(detect(&[("TERM", "st-direct")], true), ColourDepth::Rgb24);
+    assert_eq!(detect(&[("TERM", "something-new")], true), ColourDepth::Ansi);
+    assert_eq!(detect(&[("TERM", "dumb")], true), ColourDepth::None);
+}
+
+#[test]
+fn known_terminal_programs_are_truecolour() {
+    assert_eq!(detect(&[("TERM", "xterm-256color"), ("TERM_PROGRAM", "iTerm.app")], true), ColourDepth::Rgb24);
+    assert_eq!(detect(&[("WT_SESSION", "1234")], true), ColourDepth::Rgb24);
+}
+
+#[test]
+fn pipes_get_plain_text() {
+    assert_eq!(detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")], false), ColourDepth::None);
+}
+
+#[test]
+fn no_color_turns_colour_off() {
+    assert_eq!(detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor"), ("NO_COLOR", "1")], true), ColourDepth::None);
+
+    // Empty means not set
+    assert_eq!(detect(&[("TERM", "xterm-256color"), ("NO_COLOR", "")], true), ColourDepth::Ansi256);
+}
+
+#[test]
+fn force_color_wins() {
+    assert_eq!(detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor"), ("FORCE_COLOR", "1")], false), ColourDepth::Rgb24);
+    assert_eq!(detect(&[("NO_COLOR", "1"), ("FORCE_COLOR", "1")], true), ColourDepth::Ansi);
+    assert_eq!(detect(&[("FORCE_COLOR", "true")], false), ColourDepth::Ansi);
+    assert_eq!(detect(&[("FORCE_COLOR", "2")], false), ColourDepth::Ansi256);
+    assert_eq!(detect(&[("TERM", "xterm"), ("FORCE_COLOR", "3")], false), ColourDepth::Rgb24);
+}
+
+#[test]
+fn force_color_can_turn_colour_off() {
+    assert_eq!(detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor"), ("FORCE_COLOR", "0")], true), ColourDepth::None);
+    assert_eq!(detect(&[("TERM", "xterm-256color"), ("FORCE_COLOR", "false")], true), ColourDepth::None);
+}
+
+#[test]
+fn background_replies_are_parsed() {
+    // xterm answers with four hex digits per channel, ended with either BEL or ST
+    assert_eq!(parse_background_reply(b"\x1b]11;rgb:ffff/ffff/ffff\x07"), Some(Rgb24::from((255, 255, 255))));
+    assert_eq!(parse_background_reply(b"\x1b]11;rgb:1e1e/1e1e/2e2e\x1b\\"), Some(Rgb24::from((30, 30, 46))));
+
+    // Fewer digits are scaled up, rather than being the top bits
+    assert_eq!(parse_background_reply(b"\x1b]11;rgb:f/8/0\x07"), Some(Rgb24::from((255, 136, 0))));
+    assert_eq!(parse_background_reply(b"\x1b]11;rgb:12/34/56\x07"), Some(Rgb24::from((0x12, 0x34, 0x56))));
+}
+
+#[test]
+fn bad_background_replies_are_ignored() {
+    assert_eq!(parse_background_reply(b""), None);
+    assert_eq!(parse_background_reply(b"\x1b]11;rgb:ffff/ffff\x07"), None);
+    assert_eq!(parse_background_reply(b"\x1b]11;rgb:fffff/0/0\x07"), None);
+    assert_eq!(parse_background_reply(b"\x1b]11;rgb:zz/00/00\x07"), None);
+}
+
+#[test]
+fn backgrounds_can_be_given() {
+    assert_eq!("auto".parse::<Background>().unwrap(), Background::Auto);
+    assert_eq!("light".parse::<Background>().unwrap().resolve(), Some(Rgb24::from((255, 255, 255))));
+    assert_eq!("dark".parse::<Background>().unwrap().resolve(), Some(Rgb24::from((0, 0, 0))));
+    assert_eq!("#fdf6e3".parse::<Background>().unwrap().resolve(), Some(Rgb24::from((0xfd, 0xf6, 0xe3))));
+
+    assert!("grey".parse::<Background>().is_err());
+    assert!("#fff".parse::<Background>().is_err());
+}
+
+#[test]
+fn cell_size_replies_are_parsed() {
+    assert_eq!(parse_cell_size_reply(b"\x1b[6;20;10t"), Some((10, 20)));
+    assert_eq!(parse_cell_size_reply(b"\x1b[6;20;10t\x1b[?62;4c"), Some((10, 20)));
+
+    // Terminals that don't know about it only answer the device attributes
+    assert_eq!(parse_cell_size_reply(b"\x1b[?62;22c"), None);
+    assert_eq!(parse_cell_size_reply(b"\x1b[6;0;0t"), None);
+}
+
+#[test]
+fn device_attributes_are_parsed() {
+    assert_eq!(parse_attributes_reply(b"\x1b[?62;4;6;22c"), Some(vec![62, 4, 6, 22]));
+    assert_eq!(parse_attributes_reply(b"\x1b]11;rgb:0000/0000/0000\x1b\\\x1b[?1;2c"), Some(vec![1, 2]));
+
+    // Not finished yet
+    assert_eq!(parse_attributes_reply(b"\x1b[?62;4"), None);
+}
+
+/// Picks the graphics with only the given environment variables set, for a terminal that does (or
+/// doesn't) say it can show sixels.
+fn graphics(vars: &[(&str, &str)], sixel: bool) -> Option<Graphics> {
+    let var = |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string());
+
+    detect_graphics_with(var, true, || sixel)
+}
+
+#[test]
+fn graphics_go_by_the_terminal() {
+    assert_eq!(graphics(&[("TERM", "xterm-kitty")], false), Some(Graphics::Kitty));
+    assert_eq!(graphics(&[("TERM", "xterm-256color"), ("KITTY_WINDOW_ID", "1")], false), Some(Graphics::Kitty));
+    assert_eq!(graphics(&[("TERM_PROGRAM", "iTerm.app")], false), Some(Graphics::Iterm2));
+    assert_eq!(graphics(&[("TERM_PROGRAM", "WezTerm")], true), Some(Graphics::Iterm2));
+    assert_eq!(graphics(&[("TERM", "foot")], true), Some(Graphics::Sixel));
+    assert_eq!(graphics(&[("TERM", "xterm-256color")], false), None);
+}
+
+#[test]
+fn pipes_get_no_graphics() {
+    let var = |name: &str| (name == "TERM").then(|| "xterm-kitty".to_string());
+
+    assert_eq!(detect_graphics_with(var, false, || true), None);
+}
@@ -0,0 +1,171 @@
+use apixels::colors::{Ansi, Ansi256, Colour, Rgb24, ANSI_COLOURS};
+use apixels::theme::{BuiltinTheme, Theme};
+use clap::ValueEnum;
+
+fn rgb(hex: u32) -> Rgb24 {
+    Rgb24::from(((hex >> 16) as u8, (hex >> 8) as u8, hex as u8))
+}
+
+#[test]
+fn builtin_themes_go_by_name() {
+    assert_eq!(Theme::named("solarized-dark").unwrap(), BuiltinTheme::SolarizedDark.theme());
+    assert_eq!(Theme::named("Dracula").unwrap(), BuiltinTheme::Dracula.theme());
+    assert!(Theme::named("not-a-theme-or-a-file").is_err());
+}
+
+#[test]
+fn ubuntu_is_what_ansi_assumes() {
+    let theme = BuiltinTheme::Ubuntu.theme();
+
+    for colour in ANSI_COLOURS {
+        assert_eq!(theme.ansi[colour as usize].into_rgb(), colour.into_rgb());
+    }
+}
+
+#[test]
+fn every_theme_matches_its_own_colours() {
+    for builtin in BuiltinTheme::value_variants() {
+        let theme = builtin.theme();
+
+        for (index, colour) in theme.ansi.iter().enumerate() {
+            // Some themes use the same colour twice, so only the colour has to match
+            let nearest = theme.nearest_ansi(*colour);
+            assert_eq!(theme.ansi[nearest as usize], *colour, "{builtin:?} colour {index}");
+
+            // The colour cube has some of them too, like xterm's black and bright red
+            let nearest = theme.nearest_ansi256(*colour);
+            let rgb = if nearest.index < 16 { theme.ansi[nearest.index as usize] } else { Rgb24::from(nearest.into_rgb()) };
+            assert_eq!(rgb, *colour, "{builtin:?} colour {index} came out as {}", nearest.index);
+        }
+    }
+}
+
+#[test]
+fn themes_change_the_nearest_colour() {
+    // Solarized's "bright green" is actually one of its greys
+    let grey = rgb(0x586e75);
+
+    assert_eq!(BuiltinTheme::SolarizedDark.theme().nearest_ansi(grey), Ansi::Green);
+    assert_ne!(BuiltinTheme::Xterm.theme().nearest_ansi(grey), Ansi::Green);
+}
+
+#[test]
+fn the_colour_cube_still_wins_when_its_closer() {
+    let theme = BuiltinTheme::Dracula.theme();
+
+    assert_eq!(theme.nearest_ansi256(Rgb24::from((0, 0, 95))), Ansi256 { index: 17 });
+}
+
+#[test]
+fn xresources() {
+    let theme = Theme::from_xresources("
+        ! Comments are ignored
+        #define base03 #002b36
+        *background: base03
+        *.color0: #000000
+        *color1: rgb:cd/00/00
+        URxvt.color2: #00cd00
+        *.color3: #cdcd00
+        *.color4: #0000ee
+        *.color5: #cd00cd
+        *.color6: #00cdcd
+        *.color7: #e5e5e5
+        *.color8: #7f7f7f
+        *.color9: #ff0000
+        *.color10: #00ff00
+        *.color11: #ffff00
+        *.color12: #5c5cff
+        *.color13: #ff00ff
+        *.color14: #00ffff
+        *.color15: #ffffff
+    ").unwrap();
+
+    assert_eq!(theme.ansi, BuiltinTheme::Xterm.theme().ansi);
+    assert_eq!(theme.background, Some(rgb(0x002b36)));
+}
+
+#[test]
+fn missing_colours_are_an_error() {
+    let error = Theme::from_xresources("*.color0: #000000\n*.color1: #ff0000").unwrap_err();
+
+    assert!(error.to_string().contains("2, 3"), "{error}");
+}
+
+#[test]
+fn windows_terminal() {
+    let scheme = r##"{
+        "name": "Campbell",
+        "background": "#0C0C0C",
+        "black": "#0C0C0C", "red": "#C50F1F", "green": "#13A10E", "yellow": "#C19C00",
+        "blue": "#0037DA", "purple": "#881798", "cyan": "#3A96DD", "white": "#CCCCCC",
+        "brightBlack": "#767676", "brightRed": "#E74856", "brightGreen": "#16C60C", "brightYellow": "#F9F1A5",
+        "brightBlue": "#3B78FF", "brightPurple": "#B4009E", "brightCyan": "#61D6D6", "brightWhite": "#F2F2F2"
+    }"##;
+
+    let theme = Theme::from_windows_terminal(scheme).unwrap();
+
+    assert_eq!(theme.ansi[5], rgb(0x881798));
+    assert_eq!(theme.ansi[11], rgb(0xf9f1a5));
+    assert_eq!(theme.background, Some(rgb(0x0c0c0c)));
+
+    // The same scheme, inside of a settings file
+    let settings = Theme::from_windows_terminal(&format!(r#"{{ "schemes": [{scheme}] }}"#)).unwrap();
+    assert_eq!(settings, theme);
+}
+
+#[test]
+fn alacritty() {
+    let theme = Theme::from_alacritty(r##"
+        [colors.primary]
+        background = "0x282a36"
+
+        [colors.normal]
+        black = "#21222c"
+        red = "#ff5555"
+        green = "#50fa7b"
+        yellow = "#f1fa8c"
+        blue = "#bd93f9"
+        magenta = "#ff79c6"
+        cyan = "#8be9fd"
+        white = "#f8f8f2"
+
+        [colors.bright]
+        black = "#6272a4"
+        red = "#ff6e6e"
+        green = "#69ff94"
+        yellow = "#ffffa5"
+        blue = "#d6acff"
+        magenta = "#ff92df"
+        cyan = "#a4ffff"
+        white = "#ffffff"
+    "##).unwrap();
+
+    assert_eq!(theme, BuiltinTheme::Dracula.theme());
+}
+
+#[test]
+fn itermcolors() {
+    let colour = |name: String, (r, g, b): (f64, f64, f64)| format!("
+        <key>{name}</key>
+        <dict>
+            <key>Blue Component</key><real>{b}</real>
+            <key>Green Component</key><real>{g}</real>
+            <key>Red Component</key><real>{r}</real>
+        </dict>");
+
+    let colours: String = (0..16)
+        .map(|index| colour(format!("Ansi {index} Color"), (index as f64 / 15.0, 0.0, 1.0)))
+        .chain([colour("Background Color".to_string(), (0.0, 0.5, 1.0))])
+        .collect();
+
+    let plist = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+        <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+        <plist version="1.0"><dict>{colours}</dict></plist>"#);
+
+    let theme = Theme::from_itermcolors(plist.as_bytes()).unwrap();
+
+    assert_eq!(theme.ansi[0], Rgb24::from((0, 0, 255)));
+    assert_eq!(theme.ansi[3], Rgb24::from((51, 0, 255)));
+    assert_eq!(theme.ansi[15], Rgb24::from((255, 0, 255)));
+    assert_eq!(theme.background, Some(Rgb24::from((0, 128, 255))));
+}
@@ -0,0 +1,130 @@
+use apixels::transform::{orientation, Flip, Length, Region, Transform};
+use image::{imageops, Rgba, RgbaImage};
+
+/// Every pixel is a different colour, so that it's easy to tell where each one ended up.
+fn numbered(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, 0, 255]))
+}
+
+/// A TIFF header with nothing in it but an orientation tag, which is all `orientation` looks at.
+fn tiff(orientation: u16) -> Vec<u8> {
+    let mut bytes = b"II*\0".to_vec();
+
+    // The first IFD straight after the header, with one entry: tag 0x0112, a SHORT, one of them
+    bytes.extend(8u32.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend([0x12, 0x01, 3, 0]);
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(orientation.to_le_bytes());
+    bytes.extend([0, 0]);
+    // No more IFDs
+    bytes.extend(0u32.to_le_bytes());
+
+    bytes
+}
+
+#[test]
+fn lengths_are_pixels_or_percentages() {
+    assert_eq!("12".parse::<Length>().unwrap(), Length::Pixels(12));
+    assert_eq!(" 12.5% ".parse::<Length>().unwrap(), Length::Percent(12.5));
+
+    for bad in ["", "-3", "1.5", "abc%", "101%", "-1%", "%"] {
+        assert!(bad.parse::<Length>().is_err(), "`{bad}` shouldn't parse");
+    }
+}
+
+#[test]
+fn regions_need_all_four_parts() {
+    assert_eq!("1,2,50%,4".parse::<Region>().unwrap(), Region {
+        x: Length::Pixels(1),
+        y: Length::Pixels(2),
+        width: Length::Percent(50.0),
+        height: Length::Pixels(4),
+    });
+
+    for bad in ["1,2,3", "1,2,3,4,5", "", "1,2,x,4"] {
+        assert!(bad.parse::<Region>().is_err(), "`{bad}` shouldn't parse");
+    }
+}
+
+#[test]
+fn crops_stay_inside_the_image() {
+    let img = numbered(10, 8);
+    let crop = |region: &str| Transform::Crop(region.parse().unwrap()).apply(&img);
+
+    let cropped = crop("2,3,4,2");
+    assert_eq!(cropped.dimensions(), (4, 2));
+    assert_eq!(cropped.get_pixel(0, 0), &Rgba([2, 3, 0, 255]));
+
+    assert_eq!(crop("50%,50%,100%,100%").dimensions(), (5, 4));
+    assert_eq!(crop("20,20,5,5").dimensions(), (1, 1));
+    assert_eq!(crop("0,0,0,0").dimensions(), (1, 1));
+}
+
+#[test]
+fn empty_images_dont_break_anything() {
+    for (width, height) in [(0, 0), (0, 5), (5, 0)] {
+        let img = RgbaImage::new(width, height);
+
+        for transform in [Transform::Crop("1,1,50%,50%".parse().unwrap()), Transform::Rotate90, Transform::Flip(Flip::Horizontal)] {
+            transform.apply(&img);
+        }
+    }
+}
+
+#[test]
+fn right_angles_dont_resample() {
+    assert_eq!(Transform::rotate(0.0), None);
+    assert_eq!(Transform::rotate(360.0), None);
+    assert_eq!(Transform::rotate(90.0), Some(Transform::Rotate90));
+    assert_eq!(Transform::rotate(-90.0), Some(Transform::Rotate270));
+    assert_eq!(Transform::rotate(540.0), Some(Transform::Rotate180));
+    assert_eq!(Transform::rotate(45.0), Some(Transform::Rotate(45.0)));
+    assert_eq!(Transform::rotate(-45.0), Some(Transform::Rotate(315.0)));
+}
+
+#[test]
+fn rotating_makes_room_for_the_corners() {
+    assert_eq!(Transform::Rotate(45.0).apply(&numbered(10, 10)).dimensions(), (14, 14));
+    assert_eq!(Transform::Rotate(30.0).apply(&numbered(20, 10)).dimensions(), (22, 19));
+    assert_eq!(Transform::Rotate(1.0).apply(&RgbaImage::new(1, 1)).dimensions(), (1, 1));
+}
+
+#[test]
+fn rotating_leaves_the_corners_transparent() {
+    let img = RgbaImage::from_pixel(10, 10, Rgba([200, 100, 50, 255]));
+    let rotated = Transform::Rotate(45.0).apply(&img);
+
+    assert_eq!(rotated.get_pixel(0, 0)[3], 0);
+    // The middle is still the same colour, and transparent neighbours don't darken the edges
+    assert_eq!(rotated.get_pixel(7, 7), &Rgba([200, 100, 50, 255]));
+    assert!(rotated.pixels().filter(|pixel| pixel[3] != 0).all(|pixel| pixel.0[..3] == [200, 100, 50]));
+}
+
+#[test]
+fn exif_orientation_turns_images_the_right_way_up() {
+    let upright = numbered(3, 2);
+
+    let transpose = |img: &RgbaImage| RgbaImage::from_fn(img.height(), img.width(), |x, y| *img.get_pixel(y, x));
+
+    // How a camera would have stored the upright image, for each orientation
+    let stored = [
+        upright.clone(),
+        imageops::flip_horizontal(&upright),
+        imageops::rotate180(&upright),
+        imageops::flip_vertical(&upright),
+        transpose(&upright),
+        imageops::rotate270(&upright),
+        imageops::rotate180(&transpose(&upright)),
+        imageops::rotate90(&upright),
+    ];
+
+    for (orientation_tag, stored) in (1..=8).zip(stored) {
+        let fixed = orientation(&tiff(orientation_tag)).iter().fold(stored, |img, transform| transform.apply(&img));
+
+        assert_eq!(fixed, upright, "orientation {orientation_tag}");
+    }
+
+    assert!(orientation(&tiff(9)).is_empty());
+    assert!(orientation(b"not an image").is_empty());
+}